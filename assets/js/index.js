@@ -1,31 +1,68 @@
-const query = { title_type: 'movie' }
-async function submit() {
+const query = { title_type: 'movie', search: true, exclude_adult: true }
+async function submit(_ev, cursor) {
   // console.log(query)
   const results = document.getElementById('results')
-  results.innerHTML = ''
+  if (!cursor) {
+    results.innerHTML = ''
+  }
   const div = document.createElement('div')
   div.innerHTML = 'loading...'
   results.appendChild(div)
-  const res = await fetch('/api', {
-    method: 'POST',
-    headers: { 'content-type': 'application/json' },
-    body: JSON.stringify(query),
+  const params = new URLSearchParams()
+  Object.entries({ ...query, cursor }).forEach(([key, value]) => {
+    if (value === undefined || value === '') {
+      return
+    }
+    params.set(key, Array.isArray(value) ? value.join(',') : value)
   })
+  // GET so the browser can revalidate with the ETag instead of refetching
+  const res = await fetch(`/api/titles?${params}`)
   const body = await res.json()
+  div.remove()
   if (res.status >= 400) {
     console.error(body)
     return
   }
-  results.innerHTML = ''
-  body.forEach((e) => {
+  body.results.forEach((e) => {
     const a = document.createElement('a')
     a.href = `/movie/${e.tconst}`
     const li = document.createElement('li')
     li.id = e.tconst
-    li.innerHTML = `${e.title_type} ${e.start_year} ${e.primary_title}`
+    li.innerHTML = `${e.title_type} ${e.start_year} ${e.localized_title ?? e.primary_title}`
     a.appendChild(li)
     results.append(a)
   })
+  if (body.next_cursor) {
+    const more = document.createElement('button')
+    more.innerHTML = `more (${body.total})`
+    more.addEventListener('click', () => {
+      more.remove()
+      submit(null, body.next_cursor)
+    })
+    results.append(more)
+  }
+}
+
+async function suggest(text) {
+  const suggestions = document.getElementById('suggestions')
+  suggestions.innerHTML = ''
+  if (text.trim() === '') {
+    return
+  }
+  const res = await fetch(`/api/suggest?q=${encodeURIComponent(text)}`)
+  const body = await res.json()
+  if (res.status >= 400) {
+    console.error(body)
+    return
+  }
+  body.forEach((e) => {
+    const a = document.createElement('a')
+    a.href = e.kind === 'title' ? `/movie/${e.id}` : `/person/${e.id}`
+    const li = document.createElement('li')
+    li.innerHTML = e.detail ? `${e.label} (${e.detail})` : e.label
+    a.appendChild(li)
+    suggestions.append(a)
+  })
 }
 
 document.addEventListener(
@@ -40,20 +77,20 @@ document.addEventListener(
       'input',
       debounce((ev) => {
         // console.log('form', ev.target.id, ev.target.value)
+        // the full search runs on submit, typing only asks for suggestions
         if (ev.target.id === 'title') {
           parseInputQuery(query, ev.target.value)
+          suggest(query.title)
         } else {
           query[ev.target.id] = ev.target.value
         }
-        if (query.title !== '') {
-          submit(ev)
-        }
-      }, 500),
+      }, 200),
     )
     search.addEventListener('submit', (ev) => {
       ev.preventDefault()
       console.log(ev.target)
       parseInputQuery(query, document.getElementById('title').value)
+      document.getElementById('suggestions').innerHTML = ''
       if (query.title !== '') {
         submit(ev)
       }
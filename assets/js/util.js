@@ -8,9 +8,17 @@ const debounce = (callback, wait) => {
   }
 }
 
+const FILTERS = ['year:', 'type:', 'genre:', 'runtime:', 'adult:', 'region:', 'lang:']
+
+// parses `a-b`, `a-` and `-b` into [from, to], either may be undefined
+function parseRange(value) {
+  const [from, to] = value.split('-')
+  return [from ? +from : undefined, to ? +to : undefined]
+}
+
 function parseInputQuery(query, value) {
   const hasFilter = (i) => {
-    return i.startsWith('year:') || i.startsWith('type:')
+    return FILTERS.some((f) => i.startsWith(f))
   }
   const items = value.split(' ').reduce(
     (res, i) => {
@@ -22,13 +30,43 @@ function parseInputQuery(query, value) {
       title: [],
     },
   )
+  query.title_type = 'movie'
+  query.exclude_adult = true
+  delete query.year
+  delete query.from
+  delete query.to
+  delete query.runtime_min
+  delete query.runtime_max
+  delete query.region
+  delete query.lang
+  query.genres = []
   items.filters.forEach((i) => {
     if (i.startsWith('year:')) {
-      query.year = +i.substring(5)
+      const year = i.substring(5)
+      if (year.includes('-')) {
+        ;[query.from, query.to] = parseRange(year)
+      } else {
+        query.year = +year
+      }
     }
     if (i.startsWith('type:')) {
       query.title_type = i.substring(5)
     }
+    if (i.startsWith('genre:')) {
+      query.genres.push(...i.substring(6).split(',').filter((g) => g))
+    }
+    if (i.startsWith('runtime:')) {
+      ;[query.runtime_min, query.runtime_max] = parseRange(i.substring(8))
+    }
+    if (i.startsWith('adult:')) {
+      query.exclude_adult = i.substring(6) !== 'yes'
+    }
+    if (i.startsWith('region:')) {
+      query.region = i.substring(7)
+    }
+    if (i.startsWith('lang:')) {
+      query.lang = i.substring(5)
+    }
   })
   query.title = items.title.join(' ')
 }
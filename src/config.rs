@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_CONFIG: &str = "movies.toml";
+
+#[derive(Parser)]
+#[command(about = "Lets go to the movies")]
+pub struct Cli {
+    /// TOML config file, `movies.toml` is read if present
+    #[arg(short, long, env = "MOVIES_CONFIG")]
+    config: Option<PathBuf>,
+    /// sqlite database url
+    #[arg(long, env = "MOVIES_DATABASE", global = true)]
+    database: Option<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the web server
+    Serve {
+        #[arg(long, env = "MOVIES_BIND")]
+        bind: Option<SocketAddr>,
+        /// glob of Tera templates
+        #[arg(long, env = "MOVIES_TEMPLATES")]
+        templates: Option<String>,
+        /// let the session cookie go over plain HTTP, for local development
+        #[arg(long, env = "MOVIES_INSECURE_COOKIES")]
+        insecure_cookies: bool,
+    },
+    /// Load IMDb dumps into the database
+    Ingest {
+        /// directory of .tsv or .tsv.gz dumps
+        #[arg(long, env = "MOVIES_DATA_DIR")]
+        data_dir: Option<PathBuf>,
+        /// upsert, delete removed rows and resume instead of a full load
+        #[arg(long)]
+        incremental: bool,
+    },
+    /// Download IMDb dumps
+    Fetch {
+        #[arg(long, env = "MOVIES_DATA_DIR")]
+        data_dir: Option<PathBuf>,
+        /// leave files gzipped, ingest reads them directly
+        #[arg(long)]
+        keep_gz: bool,
+    },
+    /// Full-text title search
+    Search {
+        text: Vec<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Row counts for every table
+    Stats,
+}
+
+/// Settings shared by all subcommands. Flags and env vars win over the
+/// config file, which wins over the defaults.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub database: String,
+    pub bind: SocketAddr,
+    pub templates: String,
+    pub data_dir: PathBuf,
+    /// mark the session cookie `Secure`, so browsers only send it over HTTPS
+    pub secure_cookies: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database: "sqlite:movies.db".into(),
+            bind: ([127, 0, 0, 1], 3000).into(),
+            templates: "templates/**/*.html".into(),
+            data_dir: PathBuf::from("data"),
+            secure_cookies: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let mut config = match &cli.config {
+            Some(path) => Self::read(path)?,
+            None if Path::new(DEFAULT_CONFIG).exists() => Self::read(Path::new(DEFAULT_CONFIG))?,
+            None => Config::default(),
+        };
+
+        if let Some(database) = &cli.database {
+            config.database = database.clone();
+        }
+        match &cli.command {
+            Command::Serve {
+                bind,
+                templates,
+                insecure_cookies,
+            } => {
+                if let Some(bind) = bind {
+                    config.bind = *bind;
+                }
+                if let Some(templates) = templates {
+                    config.templates = templates.clone();
+                }
+                if *insecure_cookies {
+                    config.secure_cookies = false;
+                }
+            }
+            Command::Ingest { data_dir, .. } | Command::Fetch { data_dir, .. } => {
+                if let Some(data_dir) = data_dir {
+                    config.data_dir = data_dir.clone();
+                }
+            }
+            Command::Search { .. } | Command::Stats => {}
+        }
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+}
+
+#[test]
+fn test_config_precedence() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("movies.toml");
+    fs::write(
+        &path,
+        "database = \"sqlite:other.db\"\nbind = \"0.0.0.0:8080\"\n",
+    )?;
+
+    let cli = Cli::parse_from([
+        "movies",
+        "--config",
+        path.to_str().unwrap(),
+        "serve",
+        "--bind",
+        "127.0.0.1:4000",
+    ]);
+    let config = Config::load(&cli)?;
+    assert_eq!(config.database, "sqlite:other.db");
+    assert_eq!(config.bind, ([127, 0, 0, 1], 4000).into());
+    assert_eq!(config.templates, Config::default().templates);
+    assert!(config.secure_cookies);
+
+    fs::write(&path, "secure_cookies = false\n")?;
+    let cli = Cli::parse_from(["movies", "--config", path.to_str().unwrap(), "serve"]);
+    assert!(!Config::load(&cli)?.secure_cookies);
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, sqlite::SqliteRow, QueryBuilder, Row, Sqlite, SqlitePool};
+
+use super::titles::Title;
+
+/// An alternate title from `title_akas`.
+#[derive(Debug, Serialize)]
+pub struct Aka {
+    pub tconst: String,
+    pub ordering: i64,
+    pub title: String,
+    pub region: Option<String>,
+    pub language: Option<String>,
+    /// e.g. `imdbDisplay`, `original`, `working`
+    pub types: Option<String>,
+    pub attributes: Option<String>,
+    pub is_original_title: bool,
+}
+
+/// Region and language to show titles in, from `?region=FR&lang=fr`.
+#[derive(Debug, Default, Deserialize)]
+pub struct Locale {
+    pub region: Option<String>,
+    #[serde(rename = "lang")]
+    pub language: Option<String>,
+}
+
+impl Locale {
+    pub fn is_empty(&self) -> bool {
+        self.region.as_deref().unwrap_or("").is_empty()
+            && self.language.as_deref().unwrap_or("").is_empty()
+    }
+}
+
+/// `\N` in the dump means no value.
+fn optional(row: &SqliteRow, column: &str) -> Option<String> {
+    row.try_get::<String, _>(column)
+        .ok()
+        .filter(|v| !v.is_empty() && v != "\\N")
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Aka {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            tconst: row.try_get("title_id").unwrap_or("".into()),
+            ordering: row.try_get("ordering").unwrap_or(0),
+            title: row.try_get("title").unwrap_or("".into()),
+            region: optional(row, "region"),
+            language: optional(row, "language"),
+            types: optional(row, "types"),
+            attributes: optional(row, "attributes"),
+            is_original_title: row.try_get("is_original_title").unwrap_or(false),
+        })
+    }
+}
+
+pub struct AkaQuery<'a> {
+    query: QueryBuilder<'a, Sqlite>,
+    filtered: bool,
+}
+
+impl<'a> AkaQuery<'a> {
+    pub fn new() -> Self {
+        AkaQuery {
+            query: QueryBuilder::new("SELECT * FROM title_akas"),
+            filtered: false,
+        }
+    }
+
+    pub fn title(mut self, id: &'a String) -> Self {
+        if !id.is_empty() {
+            self.where_and();
+            self.query.push(" title_id = ");
+            self.query.push_bind(id);
+        }
+        self
+    }
+
+    pub fn titles(mut self, ids: &'a [String]) -> Self {
+        if !ids.is_empty() {
+            self.where_and();
+            self.query.push(" title_id IN (");
+            let mut separated = self.query.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            separated.push_unseparated(")");
+        }
+        self
+    }
+
+    pub fn region(mut self, region: Option<String>) -> Self {
+        if let Some(region) = region.filter(|r| !r.is_empty()) {
+            self.where_and();
+            self.query.push(" region = ");
+            self.query.push_bind(region);
+            self.query.push(" COLLATE NOCASE");
+        }
+        self
+    }
+
+    pub fn language(mut self, language: Option<String>) -> Self {
+        if let Some(language) = language.filter(|l| !l.is_empty()) {
+            self.where_and();
+            self.query.push(" language = ");
+            self.query.push_bind(language);
+            self.query.push(" COLLATE NOCASE");
+        }
+        self
+    }
+
+    pub fn aka_type(mut self, aka_type: String) -> Self {
+        if !aka_type.is_empty() {
+            self.where_and();
+            self.query.push(" types = ");
+            self.query.push_bind(aka_type);
+        }
+        self
+    }
+
+    pub fn locale(self, locale: &Locale) -> Self {
+        self.region(locale.region.clone())
+            .language(locale.language.clone())
+    }
+
+    fn where_and(&mut self) {
+        if !self.filtered {
+            self.query.push(" WHERE");
+            self.filtered = true;
+        } else {
+            self.query.push(" AND");
+        }
+    }
+
+    pub async fn fetch(mut self, db: &SqlitePool) -> Result<Vec<Aka>> {
+        self.query.push(" ORDER BY title_id, ordering");
+        Ok(self.query.build_query_as::<Aka>().fetch_all(db).await?)
+    }
+}
+
+/// The title to show for `locale` among a title's akas, preferring the one
+/// IMDb displays in that region.
+pub fn localized<'a>(akas: &'a [Aka], locale: &Locale) -> Option<&'a Aka> {
+    let matches = |aka: &&Aka| {
+        let matching = |want: &Option<String>, have: &Option<String>| match want.as_deref() {
+            None | Some("") => true,
+            Some(want) => have
+                .as_deref()
+                .is_some_and(|have| have.eq_ignore_ascii_case(want)),
+        };
+        matching(&locale.region, &aka.region) && matching(&locale.language, &aka.language)
+    };
+    if locale.is_empty() {
+        return None;
+    }
+    akas.iter()
+        .filter(matches)
+        .find(|aka| aka.types.as_deref() == Some("imdbDisplay"))
+        .or_else(|| akas.iter().find(matches))
+}
+
+/// Sets `localized_title` on each of `titles` that has an aka for `locale`.
+pub async fn localize(db: &SqlitePool, titles: &mut [Title], locale: &Locale) -> Result<()> {
+    if locale.is_empty() || titles.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<String> = titles.iter().map(|t| t.tconst.clone()).collect();
+    let akas = AkaQuery::new()
+        .titles(&ids)
+        .locale(locale)
+        .fetch(db)
+        .await?;
+    // sorted by title, so each title's akas are contiguous
+    for own in akas.chunk_by(|a, b| a.tconst == b.tconst) {
+        let localized = localized(own, locale).map(|aka| aka.title.clone());
+        for title in titles.iter_mut().filter(|t| t.tconst == own[0].tconst) {
+            title.localized_title = localized.clone();
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_akas() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let id = "tt0068646".to_string();
+    let french = AkaQuery::new()
+        .title(&id)
+        .region(Some("fr".into()))
+        .fetch(&pool)
+        .await?;
+    assert_eq!(french.len(), 1);
+    assert_eq!(french[0].title, "Le Parrain");
+
+    let display = AkaQuery::new()
+        .title(&id)
+        .aka_type("imdbDisplay".into())
+        .fetch(&pool)
+        .await?;
+    assert_eq!(display.len(), 4);
+
+    let locale = Locale {
+        region: Some("FR".into()),
+        language: None,
+    };
+    let mut titles = super::titles::TitleQuery::new()
+        .like_localized("Le Parrain".into(), &locale)
+        .fetch(&pool)
+        .await?;
+    assert_eq!(titles[0].tconst, "tt0068646");
+    localize(&pool, &mut titles, &locale).await?;
+    assert_eq!(titles[0].localized_title.as_deref(), Some("Le Parrain"));
+    Ok(())
+}
@@ -1,7 +1,33 @@
 use anyhow::Result;
-use sqlx::SqlitePool;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Row, SqlitePool,
+};
+use std::str::FromStr;
 
-use super::{crew, episodes, names, principals, titles};
+use super::{
+    crew, episodes, ingest, lists, names, principals, ratings, similar, suggest, titles, users,
+};
+
+/// Tables reported by `stats`.
+const TABLES: &[&str] = &[
+    "names",
+    "titles",
+    "title_akas",
+    "episodes",
+    "principals",
+    "crew",
+    "ratings",
+];
+
+/// Opens the database at `database_url`, creating it if it doesn't exist.
+pub async fn connect(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+    SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await
+}
 
 pub async fn init_tables(db: &SqlitePool) -> Result<(), sqlx::Error> {
     names::init_table(db).await?;
@@ -9,16 +35,30 @@ pub async fn init_tables(db: &SqlitePool) -> Result<(), sqlx::Error> {
     episodes::init_table(db).await?;
     principals::init_table(db).await?;
     crew::init_table(db).await?;
+    ratings::init_table(db).await?;
+    suggest::init_table(db).await?;
+    similar::init_table(db).await?;
+    ingest::init_table(db).await?;
+    lists::init_table(db).await?;
+    users::init_table(db).await?;
     Ok(())
 }
 
+pub async fn stats(db: &SqlitePool) -> Result<Vec<(&'static str, i64)>, sqlx::Error> {
+    let mut counts = vec![];
+    for table in TABLES {
+        let count = sqlx::query(&format!("SELECT count(*) AS n FROM {table}"))
+            .fetch_one(db)
+            .await?
+            .try_get("n")?;
+        counts.push((*table, count));
+    }
+    Ok(counts)
+}
+
 #[tokio::test]
 async fn test_title() -> Result<()> {
-    use sqlx::sqlite::SqlitePoolOptions;
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect("sqlite:movies.db")
-        .await?;
+    let pool = super::ingest::fixture_pool().await?;
 
     let title = sqlx::query_as::<_, super::titles::Title>(
         r#"select * from titles where tconst='tt0317705'"#,
@@ -26,13 +66,14 @@ async fn test_title() -> Result<()> {
     .fetch_one(&pool)
     .await?;
     println!("{title:?}");
+    assert_eq!(title.primary_title, "The Incredibles");
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_join() -> Result<()> {
-    use sqlx::{sqlite::SqlitePoolOptions, FromRow};
+    use sqlx::FromRow;
     #[derive(Debug, FromRow)]
     #[allow(dead_code)]
     struct Actor {
@@ -43,10 +84,7 @@ async fn test_join() -> Result<()> {
         job: Option<String>,
     }
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect("sqlite:movies.db")
-        .await?;
+    let pool = super::ingest::fixture_pool().await?;
 
     let title = sqlx::query_as::<_, super::titles::Title>(
         r#"SELECT * FROM titles AS t WHERE  primary_title LIKE ?"#,
@@ -54,8 +92,7 @@ async fn test_join() -> Result<()> {
     .bind("The Godfather")
     .fetch_one(&pool)
     .await?;
-    let actors = sqlx::query_as!(
-        Actor,
+    let actors = sqlx::query_as::<_, Actor>(
         r#"
             SELECT t.tconst, 
             n.primary_name,
@@ -67,8 +104,8 @@ async fn test_join() -> Result<()> {
             JOIN names AS n ON p.nconst = n.nconst
             WHERE t.tconst=(SELECT tconst FROM titles WHERE primary_title LIKE ?);
     "#,
-        "The Godfather",
     )
+    .bind("The Godfather")
     .fetch_all(&pool)
     .await?;
     println!("{:?}", title);
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, sqlite::SqliteRow, Row, SqlitePool};
+
+/// Someone who worked on the same titles as the person looked up.
+#[derive(Debug, Serialize)]
+pub struct Collaborator {
+    pub nconst: String,
+    /// `None` when the person is missing from the names dump
+    pub name: Option<String>,
+    /// titles they both worked on
+    pub shared: i64,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Collaborator {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            nconst: row.try_get("nconst").unwrap_or("".into()),
+            name: row.try_get("primary_name").unwrap_or(None),
+            shared: row.try_get("shared").unwrap_or(0),
+        })
+    }
+}
+
+/// A title with cast members in common with the one looked up.
+#[derive(Debug, Serialize)]
+pub struct SimilarCast {
+    pub tconst: String,
+    pub title: String,
+    pub title_type: String,
+    pub year: Option<i64>,
+    /// names of the shared cast members
+    pub cast: Vec<String>,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for SimilarCast {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let cast = row.try_get::<String, _>("shared_cast").unwrap_or_default();
+        Ok(Self {
+            tconst: row.try_get("tconst").unwrap_or("".into()),
+            title: row.try_get("primary_title").unwrap_or("".into()),
+            title_type: row.try_get("title_type").unwrap_or("".into()),
+            year: row
+                .try_get::<Option<i64>, _>("start_year")
+                .unwrap_or(None)
+                .filter(|year| *year > 0),
+            cast: serde_json::from_str(&cast).unwrap_or_default(),
+        })
+    }
+}
+
+/// Categories counted as cast rather than crew.
+pub(super) const CAST: &str = "('actor', 'actress', 'self')";
+
+/// The people `nconst` shared the most titles with, at most `limit` per
+/// category. Categories come from `principals`, plus `director` and
+/// `writer` from the crew lists.
+pub async fn collaborators(
+    db: &SqlitePool,
+    nconst: &str,
+    limit: i64,
+) -> Result<BTreeMap<String, Vec<Collaborator>>> {
+    let rows = sqlx::query(
+        r#"WITH mine AS (
+                SELECT tconst FROM principals WHERE nconst = ?1
+                UNION SELECT tconst FROM title_directors WHERE nconst = ?1
+                UNION SELECT tconst FROM title_writers WHERE nconst = ?1
+            ),
+            credits AS (
+                SELECT p.tconst, p.nconst, p.category FROM principals AS p JOIN mine USING (tconst)
+                UNION SELECT d.tconst, d.nconst, 'director' FROM title_directors AS d JOIN mine USING (tconst)
+                UNION SELECT w.tconst, w.nconst, 'writer' FROM title_writers AS w JOIN mine USING (tconst)
+            ),
+            counted AS (
+                SELECT category, nconst, count(DISTINCT tconst) AS shared
+                FROM credits WHERE nconst != ?1
+                GROUP BY category, nconst
+            )
+            SELECT * FROM (
+                SELECT c.*, n.primary_name,
+                    row_number() OVER (PARTITION BY c.category ORDER BY c.shared DESC, c.nconst) AS position
+                FROM counted AS c
+                LEFT JOIN names AS n ON n.nconst = c.nconst
+            )
+            WHERE position <= ?2
+            ORDER BY category, position"#,
+    )
+    .bind(nconst)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let mut collaborators: BTreeMap<String, Vec<Collaborator>> = BTreeMap::new();
+    for row in rows {
+        let category: String = row.try_get("category").unwrap_or("".into());
+        collaborators
+            .entry(category)
+            .or_default()
+            .push(Collaborator::from_row(&row)?);
+    }
+    Ok(collaborators)
+}
+
+/// Titles sharing the most cast members with `tconst`, more votes first
+/// among ties.
+pub async fn similar_cast(db: &SqlitePool, tconst: &str, limit: i64) -> Result<Vec<SimilarCast>> {
+    Ok(sqlx::query_as::<_, SimilarCast>(&format!(
+        r#"WITH members AS (
+                SELECT DISTINCT nconst FROM principals
+                WHERE tconst = ?1 AND category IN {CAST}
+            ),
+            shared AS (
+                SELECT DISTINCT p.tconst, p.nconst FROM principals AS p JOIN members USING (nconst)
+                WHERE p.tconst != ?1 AND p.category IN {CAST}
+            )
+            SELECT s.tconst, t.primary_title, t.title_type, t.start_year,
+                json_group_array(COALESCE(n.primary_name, s.nconst)) AS shared_cast
+            FROM shared AS s
+            JOIN titles AS t ON t.tconst = s.tconst
+            LEFT JOIN names AS n ON n.nconst = s.nconst
+            LEFT JOIN ratings AS r ON r.tconst = s.tconst
+            GROUP BY s.tconst
+            ORDER BY count(*) DESC, COALESCE(max(r.num_votes), 0) DESC, s.tconst
+            LIMIT ?2"#
+    ))
+    .bind(tconst)
+    .bind(limit)
+    .fetch_all(db)
+    .await?)
+}
+
+#[tokio::test]
+async fn test_collaborators() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let coppola = collaborators(&pool, "nm0000338", 10).await?;
+    let shared = |category: &str| {
+        coppola[category]
+            .iter()
+            .map(|c| (c.name.clone().unwrap_or_default(), c.shared))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(shared("writer"), vec![("Mario Puzo".into(), 2)]);
+    assert_eq!(
+        shared("actor"),
+        vec![
+            ("Al Pacino".into(), 2),
+            ("Robert Duvall".into(), 2),
+            ("Marlon Brando".into(), 1)
+        ]
+    );
+    assert!(!coppola.contains_key("director"));
+
+    let similar = similar_cast(&pool, "tt0068646", 10).await?;
+    assert_eq!(similar.len(), 1);
+    assert_eq!(similar[0].tconst, "tt0071562");
+    assert_eq!(similar[0].cast.len(), 3);
+    Ok(())
+}
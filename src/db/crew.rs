@@ -4,13 +4,39 @@ use sqlx::{
     prelude::FromRow, sqlite::SqliteRow, Pool, QueryBuilder, Row, Sqlite, SqlitePool, Transaction,
 };
 
-#[derive(Debug, Serialize)]
+use super::ListTable;
+
+/// Directors and writers of a title, resolved to names.
+#[derive(Debug, Default, Serialize)]
 pub struct Crew {
-    pub tconst: String,
-    pub directors: Vec<String>,
-    pub writers: Vec<String>,
+    pub directors: Vec<CrewMember>,
+    pub writers: Vec<CrewMember>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrewMember {
+    pub nconst: String,
+    /// `None` when the person is missing from the names dump
+    pub name: Option<String>,
 }
 
+pub(super) const LISTS: &[ListTable] = &[
+    ListTable {
+        table: "title_directors",
+        key: "tconst",
+        value: "nconst",
+        column: 1,
+        parent: ("crew", "directors"),
+    },
+    ListTable {
+        table: "title_writers",
+        key: "tconst",
+        value: "nconst",
+        column: 2,
+        parent: ("crew", "writers"),
+    },
+];
+
 pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     sqlx::query(
         "CREATE TABLE IF NOT EXISTS crew (
@@ -22,29 +48,38 @@ pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    for list in LISTS {
+        list.init_table(pool).await?;
+    }
+
     Ok(())
 }
 
+/// Reads the `title_directors` and `title_writers` lists joined to `names`.
 pub struct CrewQuery<'a>(QueryBuilder<'a, Sqlite>);
 
-impl<'r> FromRow<'r, SqliteRow> for Crew {
+impl<'r> FromRow<'r, SqliteRow> for CrewMember {
     fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
-        let writers_str = row.try_get("writers").unwrap_or("".to_owned());
-        let writers = writers_str.split(',').map(|s| s.to_owned()).collect();
-        let directors_str = row.try_get("directors").unwrap_or("".to_owned());
-        let directors = directors_str.split(',').map(|s| s.to_owned()).collect();
-
         Ok(Self {
-            tconst: row.try_get("tconst").unwrap_or("".into()),
-            writers,
-            directors,
+            nconst: row.try_get("nconst").unwrap_or("".into()),
+            name: row.try_get("primary_name").unwrap_or(None),
         })
     }
 }
 
 impl<'a> CrewQuery<'a> {
     pub fn new() -> Self {
-        CrewQuery(QueryBuilder::new("SELECT * FROM crew"))
+        CrewQuery(QueryBuilder::new(
+            r#"SELECT * FROM (
+                SELECT 'director' AS job, l.rowid AS position, l.tconst, l.nconst, n.primary_name
+                    FROM title_directors AS l
+                    LEFT JOIN names AS n ON n.nconst = l.nconst
+                UNION ALL
+                SELECT 'writer' AS job, l.rowid AS position, l.tconst, l.nconst, n.primary_name
+                    FROM title_writers AS l
+                    LEFT JOIN names AS n ON n.nconst = l.nconst
+            )"#,
+        ))
     }
 
     pub fn id(mut self, id: &'a String) -> Self {
@@ -64,26 +99,40 @@ impl<'a> CrewQuery<'a> {
         }
     }
 
-    pub async fn fetch_one(mut self, db: &SqlitePool) -> Result<Crew> {
-        Ok(self.0.build_query_as::<Crew>().fetch_one(db).await?)
+    /// Empty lists when the title has no crew.
+    pub async fn fetch(mut self, db: &SqlitePool) -> Result<Crew> {
+        self.0.push(" ORDER BY position");
+        let rows = self.0.build().fetch_all(db).await?;
+
+        let mut crew = Crew::default();
+        for row in rows {
+            let member = CrewMember::from_row(&row)?;
+            match row.try_get::<&str, _>("job")? {
+                "director" => crew.directors.push(member),
+                _ => crew.writers.push(member),
+            }
+        }
+        Ok(crew)
     }
 }
 
 pub async fn ingest(
     transaction: &mut Transaction<'_, Sqlite>,
-    record: &Vec<String>,
+    batch: &[Vec<String>],
 ) -> Result<(), sqlx::Error> {
-    if record.len() >= 3 {
-        sqlx::query(
-            "INSERT OR IGNORE INTO crew 
-                (tconst, directors, writers)
-                VALUES (?, ?, ?)",
-        )
-        .bind(&record[0])
-        .bind(&record[1])
-        .bind(&record[2])
-        .execute(&mut **transaction)
-        .await?;
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 3).collect();
+    for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query =
+            QueryBuilder::new("INSERT OR REPLACE INTO crew (tconst, directors, writers) ");
+        query.push_values(chunk, |mut row, record| {
+            row.push_bind(&record[0])
+                .push_bind(&record[1])
+                .push_bind(&record[2]);
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    for list in LISTS {
+        list.ingest(transaction, &records).await?;
     }
     Ok(())
 }
@@ -1,4 +1,31 @@
-use sqlx::{Pool, Sqlite, Transaction};
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{
+    prelude::FromRow, sqlite::SqliteRow, Pool, QueryBuilder, Row, Sqlite, SqlitePool, Transaction,
+};
+
+#[derive(Debug, Serialize)]
+pub struct Episode {
+    pub tconst: String,
+    pub parent_tconst: String,
+    pub season_number: Option<i64>,
+    pub episode_number: Option<i64>,
+    pub title: String,
+    pub year: i64,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Episode {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            tconst: row.try_get("tconst").unwrap_or("".into()),
+            parent_tconst: row.try_get("parentTconst").unwrap_or("".into()),
+            season_number: row.try_get("seasonNumber").unwrap_or(None),
+            episode_number: row.try_get("episodeNumber").unwrap_or(None),
+            title: row.try_get("primary_title").unwrap_or("".into()),
+            year: row.try_get("start_year").unwrap_or(0),
+        })
+    }
+}
 
 pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     sqlx::query(
@@ -12,33 +39,128 @@ pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query("CREATE INDEX IF NOT EXISTS episodes_parent ON episodes (parentTconst)")
+        .execute(pool)
+        .await?;
+
     Ok(())
 }
 
+pub struct EpisodeQuery<'a> {
+    query: QueryBuilder<'a, Sqlite>,
+    filtered: bool,
+}
+
+impl<'a> EpisodeQuery<'a> {
+    pub fn new() -> Self {
+        EpisodeQuery {
+            query: QueryBuilder::new(
+                r#"SELECT e.*, t.primary_title, t.start_year FROM episodes AS e
+                    LEFT JOIN titles AS t ON t.tconst = e.tconst"#,
+            ),
+            filtered: false,
+        }
+    }
+
+    pub fn id(mut self, id: &'a String) -> Self {
+        if !id.is_empty() {
+            self.where_and();
+            self.query.push(" e.tconst = ");
+            self.query.push_bind(id);
+        }
+        self
+    }
+
+    pub fn series(mut self, id: &'a String) -> Self {
+        if !id.is_empty() {
+            self.where_and();
+            self.query.push(" e.parentTconst = ");
+            self.query.push_bind(id);
+        }
+        self
+    }
+
+    fn where_and(&mut self) {
+        if !self.filtered {
+            self.query.push(" WHERE");
+            self.filtered = true;
+        } else {
+            self.query.push(" AND");
+        }
+    }
+
+    pub async fn fetch_optional(mut self, db: &SqlitePool) -> Result<Option<Episode>> {
+        Ok(self
+            .query
+            .build_query_as::<Episode>()
+            .fetch_optional(db)
+            .await?)
+    }
+    pub async fn fetch(mut self, db: &SqlitePool) -> Result<Vec<Episode>> {
+        // unknown seasons and episode numbers sort last
+        self.query.push(
+            r#" ORDER BY e.seasonNumber IS NULL, e.seasonNumber,
+                e.episodeNumber IS NULL, e.episodeNumber"#,
+        );
+        Ok(self.query.build_query_as::<Episode>().fetch_all(db).await?)
+    }
+}
+
 pub async fn ingest(
     transaction: &mut Transaction<'_, Sqlite>,
-    record: &Vec<String>,
+    batch: &[Vec<String>],
 ) -> Result<(), sqlx::Error> {
-    if record.len() >= 4 {
-        sqlx::query(
-            "INSERT OR IGNORE INTO episodes 
-                            (tconst, parentTconst, seasonNumber, episodeNumber)
-                            VALUES (?, ?, ?, ?)",
-        )
-        .bind(&record[0])
-        .bind(&record[1])
-        .bind(if record[2] == "\\N" {
-            None
-        } else {
-            record[2].parse::<i32>().ok()
-        })
-        .bind(if record[3] == "\\N" {
-            None
-        } else {
-            record[3].parse::<i32>().ok()
-        })
-        .execute(&mut **transaction)
-        .await?;
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 4).collect();
+    for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            "INSERT OR REPLACE INTO episodes (tconst, parentTconst, seasonNumber, episodeNumber) ",
+        );
+        query.push_values(chunk, |mut row, record| {
+            row.push_bind(&record[0])
+                .push_bind(&record[1])
+                .push_bind(if record[2] == "\\N" {
+                    None
+                } else {
+                    record[2].parse::<i32>().ok()
+                })
+                .push_bind(if record[3] == "\\N" {
+                    None
+                } else {
+                    record[3].parse::<i32>().ok()
+                });
+        });
+        query.build().execute(&mut **transaction).await?;
     }
     Ok(())
 }
+
+#[tokio::test]
+async fn test_episode_query() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let episodes = EpisodeQuery::new()
+        .series(&"tt0903747".into())
+        .fetch(&pool)
+        .await?;
+    let tconsts: Vec<_> = episodes.iter().map(|e| e.tconst.as_str()).collect();
+    assert_eq!(tconsts, vec!["tt0959621", "tt1054724", "tt1232244"]);
+    assert_eq!(episodes[1].title, "Cat's in the Bag...");
+
+    let pilot = EpisodeQuery::new()
+        .id(&"tt0959621".into())
+        .fetch_optional(&pool)
+        .await?
+        .unwrap();
+    assert_eq!(pilot.parent_tconst, "tt0903747");
+    assert_eq!(
+        (pilot.season_number, pilot.episode_number),
+        (Some(1), Some(1))
+    );
+
+    assert!(EpisodeQuery::new()
+        .id(&"tt0068646".into())
+        .fetch_optional(&pool)
+        .await?
+        .is_none());
+    Ok(())
+}
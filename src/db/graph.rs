@@ -0,0 +1,259 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Result;
+use futures::TryStreamExt;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tracing::info;
+
+use super::{names, titles};
+
+/// People and titles linked by `principals`, held in memory for path
+/// searches. Built once from the database, so it doesn't see later ingests
+/// until the server restarts.
+#[derive(Default)]
+pub struct Graph {
+    people: HashMap<String, u32>,
+    nconsts: Vec<String>,
+    tconsts: Vec<String>,
+    /// titles of each person, by index
+    person_titles: Vec<Vec<u32>>,
+    /// people of each title, by index
+    title_people: Vec<Vec<u32>>,
+}
+
+/// One link of a [`Connection`]: `title` joins the previous person to `to`.
+#[derive(Debug, Serialize)]
+pub struct Link {
+    pub tconst: String,
+    pub title: Option<String>,
+    pub year: Option<i64>,
+    pub to: Person,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Person {
+    pub nconst: String,
+    pub name: Option<String>,
+}
+
+/// Shortest chain of shared titles between two people.
+#[derive(Debug, Serialize)]
+pub struct Connection {
+    pub from: Person,
+    pub links: Vec<Link>,
+    /// number of titles in the chain
+    pub degrees: usize,
+}
+
+/// Where a person was reached from during the search.
+#[derive(Clone, Copy)]
+struct Step {
+    person: u32,
+    title: u32,
+}
+
+impl Graph {
+    pub async fn load(db: &SqlitePool) -> Result<Self> {
+        let mut graph = Graph::default();
+        let mut titles: HashMap<String, u32> = HashMap::new();
+
+        let mut rows =
+            sqlx::query_as::<_, (String, String)>("SELECT DISTINCT nconst, tconst FROM principals")
+                .fetch(db);
+        while let Some((nconst, tconst)) = rows.try_next().await? {
+            graph.add(&mut titles, nconst, tconst);
+        }
+        info!(
+            "loaded graph of {} people and {} titles",
+            graph.nconsts.len(),
+            graph.tconsts.len()
+        );
+        Ok(graph)
+    }
+
+    fn add(&mut self, titles: &mut HashMap<String, u32>, nconst: String, tconst: String) {
+        let person = *self.people.entry(nconst.clone()).or_insert_with(|| {
+            self.nconsts.push(nconst);
+            self.person_titles.push(vec![]);
+            self.nconsts.len() as u32 - 1
+        });
+        let title = *titles.entry(tconst.clone()).or_insert_with(|| {
+            self.tconsts.push(tconst);
+            self.title_people.push(vec![]);
+            self.tconsts.len() as u32 - 1
+        });
+        self.person_titles[person as usize].push(title);
+        self.title_people[title as usize].push(person);
+    }
+
+    /// Expands every person in `frontier` by one title, recording how each
+    /// newly reached person was found. Returns a person also reached from
+    /// the other side, if any.
+    fn expand(
+        &self,
+        frontier: &mut VecDeque<u32>,
+        reached: &mut HashMap<u32, Option<Step>>,
+        other: &HashMap<u32, Option<Step>>,
+    ) -> Option<u32> {
+        for _ in 0..frontier.len() {
+            let Some(person) = frontier.pop_front() else {
+                break;
+            };
+            for &title in &self.person_titles[person as usize] {
+                for &next in &self.title_people[title as usize] {
+                    if reached.contains_key(&next) {
+                        continue;
+                    }
+                    reached.insert(next, Some(Step { person, title }));
+                    if other.contains_key(&next) {
+                        return Some(next);
+                    }
+                    frontier.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Bidirectional breadth first search, growing the smaller side each
+    /// round. Returns the titles and people after `from`, as
+    /// `(tconst, nconst)` pairs, or `None` when they aren't connected.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<(String, String)>> {
+        let (&start, &goal) = (self.people.get(from)?, self.people.get(to)?);
+        if start == goal {
+            return Some(vec![]);
+        }
+        let mut forward = HashMap::from([(start, None)]);
+        let mut backward = HashMap::from([(goal, None)]);
+        let mut forward_frontier = VecDeque::from([start]);
+        let mut backward_frontier = VecDeque::from([goal]);
+
+        let meeting = loop {
+            if forward_frontier.is_empty() || backward_frontier.is_empty() {
+                return None;
+            }
+            let meeting = if forward_frontier.len() <= backward_frontier.len() {
+                self.expand(&mut forward_frontier, &mut forward, &backward)
+            } else {
+                self.expand(&mut backward_frontier, &mut backward, &forward)
+            };
+            if let Some(meeting) = meeting {
+                break meeting;
+            }
+        };
+
+        // walk back to `from`, then forward to `to`
+        let mut links = vec![];
+        let mut person = meeting;
+        while let Some(Some(step)) = forward.get(&person) {
+            links.push((step.title, person));
+            person = step.person;
+        }
+        links.reverse();
+        let mut person = meeting;
+        while let Some(Some(step)) = backward.get(&person) {
+            links.push((step.title, step.person));
+            person = step.person;
+        }
+        Some(
+            links
+                .into_iter()
+                .map(|(title, person)| {
+                    (
+                        self.tconsts[title as usize].clone(),
+                        self.nconsts[person as usize].clone(),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Finds the shortest chain between `from` and `to` and resolves its names
+/// and titles. `None` when either isn't in any title or they aren't
+/// connected.
+pub async fn connect(
+    db: &SqlitePool,
+    graph: &Graph,
+    from: &str,
+    to: &str,
+) -> Result<Option<Connection>> {
+    let Some(path) = graph.path(from, to) else {
+        return Ok(None);
+    };
+
+    let tconsts: Vec<String> = path.iter().map(|(t, _)| t.clone()).collect();
+    let mut nconsts: Vec<String> = path.iter().map(|(_, n)| n.clone()).collect();
+    nconsts.push(from.to_string());
+    // an empty id list would match every title
+    let titles: HashMap<String, titles::Title> = if tconsts.is_empty() {
+        HashMap::new()
+    } else {
+        titles::TitleQuery::new()
+            .ids(&tconsts)
+            .fetch(db)
+            .await?
+            .into_iter()
+            .map(|t| (t.tconst.clone(), t))
+            .collect()
+    };
+    let names: HashMap<String, String> = names::NameQuery::new()
+        .ids(&nconsts)
+        .fetch(db)
+        .await?
+        .into_iter()
+        .map(|n| (n.nconst, n.primary_name))
+        .collect();
+    let person = |nconst: String| Person {
+        name: names.get(&nconst).cloned(),
+        nconst,
+    };
+
+    let links: Vec<Link> = path
+        .into_iter()
+        .map(|(tconst, nconst)| {
+            let title = titles.get(&tconst);
+            Link {
+                title: title.map(|t| t.primary_title.clone()),
+                year: title.map(|t| t.start_year).filter(|year| *year > 0),
+                tconst,
+                to: person(nconst),
+            }
+        })
+        .collect();
+    Ok(Some(Connection {
+        from: person(from.to_string()),
+        degrees: links.len(),
+        links,
+    }))
+}
+
+#[tokio::test]
+async fn test_connect() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+    let graph = Graph::load(&pool).await?;
+
+    // Krazy-8 only shares an episode with Walter White, who shares the
+    // series with its creator
+    let connection = connect(&pool, &graph, "nm9999999", "nm0319213")
+        .await?
+        .unwrap();
+    assert_eq!(connection.degrees, 2);
+    assert_eq!(connection.from.name, None);
+    assert_eq!(connection.links[0].tconst, "tt1054724");
+    assert_eq!(
+        connection.links[0].to.name.as_deref(),
+        Some("Bryan Cranston")
+    );
+    assert_eq!(connection.links[1].to.nconst, "nm0319213");
+
+    let same = connect(&pool, &graph, "nm0000008", "nm0000008").await?;
+    assert_eq!(same.unwrap().degrees, 0);
+
+    // The Godfather and The Incredibles share nobody
+    assert!(connect(&pool, &graph, "nm0000008", "nm0083348")
+        .await?
+        .is_none());
+    Ok(())
+}
@@ -1,127 +1,757 @@
 use anyhow::Result;
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, Transaction};
+use flate2::read::MultiGzDecoder;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, QueryBuilder, Row, Sqlite, Transaction};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::sync::mpsc;
 
-use super::{crew, episodes, names, principals, titles};
+use super::{crew, episodes, names, principals, ratings, similar, suggest, titles, ListTable};
+
+/// IMDb dataset files and the table each one is ingested into.
+pub const IMDB_FILES: &[(&str, &str)] = &[
+    ("name.basics", "names"),
+    ("title.akas", "title_akas"),
+    ("title.basics", "titles"),
+    ("title.episode", "episodes"),
+    ("title.principals", "principals"),
+    ("title.crew", "crew"),
+    ("title.ratings", "ratings"),
+];
 
 pub struct IngestClient {
     pool: Pool<Sqlite>,
+    data_dir: PathBuf,
+    incremental: bool,
+}
+
+/// Finds `{name}.tsv` or `{name}.tsv.gz` in `dir`, preferring the plain file.
+fn locate(dir: &Path, name: &str) -> Option<PathBuf> {
+    [format!("{name}.tsv"), format!("{name}.tsv.gz")]
+        .into_iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.is_file())
+}
+
+fn open(filename: &str) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(filename)?;
+    if filename.ends_with(".gz") {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Sent from the per-file parser tasks to the single database writer.
+enum Parsed {
+    Batch {
+        filename: String,
+        table_name: &'static str,
+        records: Vec<Vec<String>>,
+        lines_committed: usize,
+    },
+    Done {
+        filename: String,
+        table_name: &'static str,
+    },
+    /// the file couldn't be read to the end
+    Failed {
+        filename: String,
+        table_name: &'static str,
+        error: io::Error,
+    },
+}
+
+/// Reads `filename` on a blocking thread, skipping the header and the first
+/// `skip` records, and sends it to the writer in `INGEST_BATCH_SIZE` batches.
+fn parse_file(
+    filename: String,
+    table_name: &'static str,
+    skip: usize,
+    sender: mpsc::Sender<Parsed>,
+) -> io::Result<()> {
+    let reader = open(&filename)?;
+    let mut records: Vec<Vec<String>> = Vec::with_capacity(super::INGEST_BATCH_SIZE);
+    let mut lines_committed = skip;
+    let mut invalid = 0;
+
+    // read errors end the file, a line that isn't UTF-8 is only skipped
+    for line in reader.split(b'\n').skip(1 + skip) {
+        lines_committed += 1;
+        let Ok(line) = String::from_utf8(line?) else {
+            invalid += 1;
+            continue;
+        };
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        records.push(line.split('\t').map(|s| s.to_string()).collect());
+
+        if records.len() >= super::INGEST_BATCH_SIZE {
+            let batch = Parsed::Batch {
+                filename: filename.clone(),
+                table_name,
+                records: std::mem::replace(
+                    &mut records,
+                    Vec::with_capacity(super::INGEST_BATCH_SIZE),
+                ),
+                lines_committed,
+            };
+            if sender.blocking_send(batch).is_err() {
+                // writer gave up, nothing left to do
+                return Ok(());
+            }
+        }
+    }
+
+    if invalid > 0 {
+        eprintln!(
+            "{}: Skipped {} lines that aren't UTF-8",
+            table_name, invalid
+        );
+    }
+    if !records.is_empty() {
+        let batch = Parsed::Batch {
+            filename: filename.clone(),
+            table_name,
+            records,
+            lines_committed,
+        };
+        if sender.blocking_send(batch).is_err() {
+            return Ok(());
+        }
+    }
+
+    let _ = sender.blocking_send(Parsed::Done {
+        filename,
+        table_name,
+    });
+    Ok(())
+}
+
+/// Tracks how far each dump file got so an incremental ingest can skip
+/// unchanged files, resume after a crash and find rows IMDb removed.
+pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS ingest_files (
+                filename TEXT PRIMARY KEY,
+                checksum TEXT,
+                lines_committed INTEGER,
+                completed INTEGER
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE TABLE IF NOT EXISTS ingest_runs (finished_at INTEGER NOT NULL)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS ingest_seen (
+                table_name TEXT,
+                key TEXT,
+                PRIMARY KEY (table_name, key)
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Unix time the last ingest finished, `None` if there hasn't been one.
+pub async fn last_ingest(pool: &Pool<Sqlite>) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT max(finished_at) FROM ingest_runs")
+        .fetch_one(pool)
+        .await
+}
+
+/// Primary key columns of each ingested table, used to match dump rows
+/// against what is already stored.
+fn key_columns(table_name: &str) -> &'static [&'static str] {
+    match table_name {
+        "names" => &["nconst"],
+        "title_akas" => &["title_id", "ordering"],
+        "principals" => &["tconst", "ordering"],
+        _ => &["tconst"],
+    }
+}
+
+/// Normalized list tables filled from the same dump as `table_name`.
+fn list_tables(table_name: &str) -> &'static [ListTable] {
+    match table_name {
+        "titles" => titles::LISTS,
+        "names" => names::LISTS,
+        "crew" => crew::LISTS,
+        _ => &[],
+    }
+}
+
+fn checksum(filename: &str) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(filename)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
 impl IngestClient {
     pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
-            .await?;
+        let pool = super::connect(database_url).await?;
 
         sqlx::query("PRAGMA journal_mode=WAL;")
             .execute(&pool)
             .await?;
 
-        Ok(IngestClient { pool })
+        Self::with_pool(pool).await
     }
 
-    pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // Process all files
-        let files = vec![
-            ("data/name.basics.tsv", "names"),
-            ("data/title.akas.tsv", "title_akas"),
-            ("data/title.basics.tsv", "titles"),
-            ("data/title.episode.tsv", "episodes"),
-            ("data/title.principals.tsv", "principals"),
-            ("data/title.crew.tsv", "crew"),
-        ];
+    pub async fn with_pool(pool: Pool<Sqlite>) -> Result<Self, sqlx::Error> {
+        super::init_tables(&pool).await?;
+
+        Ok(IngestClient {
+            pool,
+            data_dir: PathBuf::from("data"),
+            incremental: false,
+        })
+    }
+
+    /// Directory holding the `.tsv` or `.tsv.gz` dumps, `data` by default.
+    pub fn data_dir(mut self, data_dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = data_dir.into();
+        self
+    }
 
-        for (filename, table_name) in files {
-            if let Err(e) = self.process_file(filename, table_name).await {
-                eprintln!("Error processing {}: {}", filename, e);
+    /// Upsert changed rows, delete rows missing from the dump and resume
+    /// from the last committed batch instead of starting over.
+    pub fn incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        // Each file is parsed on its own thread; one writer owns the database
+        let (sender, mut receiver) = mpsc::channel(super::INGEST_CHANNEL_SIZE);
+        let mut parsers = vec![];
+        let mut failed = vec![];
+        for &(name, table_name) in IMDB_FILES {
+            let Some(path) = locate(&self.data_dir, name) else {
+                eprintln!(
+                    "Skipping {}: not found in {}",
+                    name,
+                    self.data_dir.display()
+                );
+                continue;
+            };
+            let filename = path.display().to_string();
+            let skip = match self.resume_point_for(&filename, table_name).await {
+                Ok(Some(skip)) => skip,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("Error processing {}: {}", filename, e);
+                    failed.push(filename);
+                    continue;
+                }
+            };
+            println!("Processing {}", filename);
+            let sender = sender.clone();
+            parsers.push(tokio::task::spawn_blocking(move || {
+                if let Err(error) = parse_file(filename.clone(), table_name, skip, sender.clone()) {
+                    let _ = sender.blocking_send(Parsed::Failed {
+                        filename,
+                        table_name,
+                        error,
+                    });
+                }
+            }));
+        }
+        drop(sender);
+
+        let started = Instant::now();
+        let mut totals: HashMap<&'static str, usize> = HashMap::new();
+        while let Some(parsed) = receiver.recv().await {
+            match parsed {
+                Parsed::Batch {
+                    filename,
+                    table_name,
+                    records,
+                    lines_committed,
+                } => {
+                    let batch_started = Instant::now();
+                    self.commit_batch(&filename, table_name, &records, lines_committed)
+                        .await?;
+
+                    let total = totals.entry(table_name).or_default();
+                    *total += records.len();
+                    println!(
+                        "{}: Processed {} records ({:.0} rows/sec)",
+                        table_name,
+                        total,
+                        records.len() as f64 / batch_started.elapsed().as_secs_f64()
+                    );
+                }
+                Parsed::Done {
+                    filename,
+                    table_name,
+                } => {
+                    if self.incremental {
+                        self.finish_file(&filename, table_name).await?;
+                    }
+                }
+                Parsed::Failed {
+                    filename,
+                    table_name,
+                    error,
+                } => {
+                    // what was read is kept, but nothing is deleted
+                    eprintln!("Error processing {}: {}", filename, error);
+                    if self.incremental {
+                        self.abandon_file(&filename, table_name).await?;
+                    }
+                    failed.push(filename);
+                }
             }
         }
+        for parser in parsers {
+            parser.await?;
+        }
+
+        let total: usize = totals.values().sum();
+        let elapsed = started.elapsed().as_secs_f64();
+        println!(
+            "Ingested {} records in {:.1}s ({:.0} rows/sec)",
+            total,
+            elapsed,
+            total as f64 / elapsed
+        );
+
+        println!("Building title search index");
+        titles::build_search_index(&self.pool).await?;
+        println!("Building suggestion index");
+        suggest::build_index(&self.pool).await?;
+        println!("Building similarity features");
+        similar::build_features(&self.pool).await?;
+
+        sqlx::query(
+            "INSERT INTO ingest_runs (finished_at) VALUES (CAST(strftime('%s', 'now') AS INTEGER))",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if !failed.is_empty() {
+            anyhow::bail!("could not read {}", failed.join(", "));
+        }
         Ok(())
     }
 
     async fn ingest_batch(
         transaction: &mut Transaction<'_, Sqlite>,
         table_name: &str,
-        batch: &Vec<Vec<String>>,
+        batch: &[Vec<String>],
     ) -> Result<(), sqlx::Error> {
         match table_name {
-            "title_akas" => {
-                for record in batch {
-                    titles::ingest_aka(transaction, record).await?;
-                }
-            }
-            "titles" => {
-                for record in batch {
-                    titles::ingest(transaction, record).await?;
-                }
-            }
-            "names" => {
-                for record in batch {
-                    names::ingest(transaction, record).await?;
-                }
-            }
-            "episodes" => {
-                for record in batch {
-                    episodes::ingest(transaction, record).await?;
-                }
-            }
-            "principals" => {
-                for record in batch {
-                    principals::ingest(transaction, record).await?;
-                }
-            }
-
-            "crew" => {
-                for record in batch {
-                    crew::ingest(transaction, record).await?;
-                }
-            }
+            "title_akas" => titles::ingest_aka(transaction, batch).await?,
+            "titles" => titles::ingest(transaction, batch).await?,
+            "names" => names::ingest(transaction, batch).await?,
+            "episodes" => episodes::ingest(transaction, batch).await?,
+            "principals" => principals::ingest(transaction, batch).await?,
+            "crew" => crew::ingest(transaction, batch).await?,
+            "ratings" => ratings::ingest(transaction, batch).await?,
             _ => {}
         }
         Ok(())
     }
 
-    pub async fn process_file(
+    async fn mark_seen(
+        transaction: &mut Transaction<'_, Sqlite>,
+        table_name: &str,
+        batch: &[Vec<String>],
+    ) -> Result<(), sqlx::Error> {
+        let key_len = key_columns(table_name).len();
+        let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= key_len).collect();
+        for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+            let mut query =
+                QueryBuilder::new("INSERT OR IGNORE INTO ingest_seen (table_name, key) ");
+            query.push_values(chunk, |mut row, record| {
+                row.push_bind(table_name)
+                    .push_bind(record[..key_len].join("\t"));
+            });
+            query.build().execute(&mut **transaction).await?;
+        }
+        Ok(())
+    }
+
+    /// Where to start reading `filename`: `Some(0)` for a full ingest, the
+    /// committed line count when resuming, or `None` when it is unchanged.
+    async fn resume_point_for(&self, filename: &str, table_name: &str) -> Result<Option<usize>> {
+        if !self.incremental {
+            return Ok(Some(0));
+        }
+        let checksum = checksum(filename)?;
+        let skip = self.resume_point(filename, table_name, &checksum).await?;
+        match skip {
+            None => println!("{}: Unchanged since last ingest", table_name),
+            Some(0) => {}
+            Some(skip) => println!("{}: Resuming after {} records", table_name, skip),
+        }
+        Ok(skip)
+    }
+
+    /// Returns how many lines of `filename` are already committed, resetting
+    /// the progress if the file changed since the last run.
+    async fn resume_point(
         &self,
         filename: &str,
         table_name: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Processing {}", filename);
-        let file = File::open(filename)?;
-        let reader = BufReader::new(file);
-        let mut batch: Vec<Vec<String>> = Vec::with_capacity(super::INGEST_BATCH_SIZE);
-        let mut total_records = 0;
-
-        for line in reader.lines().skip(1) {
-            if let Ok(line) = line {
-                let fields: Vec<String> = line.split('\t').map(|s| s.to_string()).collect();
-
-                batch.push(fields);
-
-                if batch.len() >= super::INGEST_BATCH_SIZE {
-                    let mut transaction = self.pool.begin().await?;
-                    Self::ingest_batch(&mut transaction, table_name, &batch).await?;
-                    transaction.commit().await?;
-
-                    total_records += batch.len();
-                    println!("{}: Processed {} records", table_name, total_records);
-                    batch.clear();
+        checksum: &str,
+    ) -> Result<Option<usize>, sqlx::Error> {
+        let progress = sqlx::query(
+            "SELECT checksum, lines_committed, completed FROM ingest_files WHERE filename = ?",
+        )
+        .bind(filename)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = progress {
+            if row.try_get::<String, _>("checksum")? == checksum {
+                if row.try_get::<bool, _>("completed")? {
+                    return Ok(None);
                 }
+                return Ok(Some(row.try_get::<i64, _>("lines_committed")? as usize));
             }
         }
 
-        if !batch.is_empty() {
-            let mut transaction = self.pool.begin().await?;
-            Self::ingest_batch(&mut transaction, table_name, &batch).await?;
-            transaction.commit().await?;
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO ingest_files
+                (filename, checksum, lines_committed, completed)
+                VALUES (?, ?, 0, 0)",
+        )
+        .bind(filename)
+        .bind(checksum)
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query("DELETE FROM ingest_seen WHERE table_name = ?")
+            .bind(table_name)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(Some(0))
+    }
+
+    /// Deletes rows that were not in the dump and marks the file done.
+    async fn finish_file(&self, filename: &str, table_name: &str) -> Result<(), sqlx::Error> {
+        let key = key_columns(table_name)
+            .iter()
+            .map(|column| format!("{table_name}.{column}"))
+            .collect::<Vec<_>>()
+            .join(" || char(9) || ");
 
-            total_records += batch.len();
-            println!("{}: Processed {} records", table_name, total_records);
+        let mut transaction = self.pool.begin().await?;
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM {table_name} WHERE NOT EXISTS (
+                SELECT 1 FROM ingest_seen AS s WHERE s.table_name = ? AND s.key = {key}
+            )"
+        ))
+        .bind(table_name)
+        .execute(&mut *transaction)
+        .await?;
+        for list in list_tables(table_name) {
+            let (child, parent_key) = (list.table, list.key);
+            sqlx::query(&format!(
+                "DELETE FROM {child} WHERE NOT EXISTS (
+                    SELECT 1 FROM {table_name} AS p WHERE p.{parent_key} = {child}.{parent_key}
+                )"
+            ))
+            .execute(&mut *transaction)
+            .await?;
         }
+        sqlx::query("DELETE FROM ingest_seen WHERE table_name = ?")
+            .bind(table_name)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("UPDATE ingest_files SET completed = 1 WHERE filename = ?")
+            .bind(filename)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
 
+        println!(
+            "{}: Removed {} records missing from dump",
+            table_name,
+            deleted.rows_affected()
+        );
         Ok(())
     }
+
+    /// Forgets the checksum and progress of a file that wasn't read to the
+    /// end, so the next run reads it again in full instead of treating it as
+    /// done or deleting rows past the failure.
+    async fn abandon_file(&self, filename: &str, table_name: &str) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM ingest_files WHERE filename = ?")
+            .bind(filename)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("DELETE FROM ingest_seen WHERE table_name = ?")
+            .bind(table_name)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await
+    }
+
+    async fn commit_batch(
+        &self,
+        filename: &str,
+        table_name: &str,
+        batch: &[Vec<String>],
+        lines_committed: usize,
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        Self::ingest_batch(&mut transaction, table_name, batch).await?;
+        if self.incremental {
+            Self::mark_seen(&mut transaction, table_name, batch).await?;
+            sqlx::query("UPDATE ingest_files SET lines_committed = ? WHERE filename = ?")
+                .bind(lines_committed as i64)
+                .bind(filename)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await
+    }
+}
+
+/// In-memory database loaded from the checked-in `fixtures` directory.
+#[cfg(test)]
+pub async fn fixture_pool() -> Result<Pool<Sqlite>> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await?;
+    IngestClient::with_pool(pool.clone())
+        .await?
+        .data_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures"))
+        .start()
+        .await?;
+    Ok(pool)
+}
+
+#[cfg(test)]
+async fn count(pool: &Pool<Sqlite>, table_name: &str) -> Result<i64> {
+    Ok(
+        sqlx::query(&format!("SELECT count(*) AS n FROM {table_name}"))
+            .fetch_one(pool)
+            .await?
+            .try_get("n")?,
+    )
+}
+
+#[tokio::test]
+async fn test_ingest_fixtures() -> Result<()> {
+    let pool = fixture_pool().await?;
+
+    assert_eq!(count(&pool, "names").await?, 14);
+    assert_eq!(count(&pool, "titles").await?, 8);
+    assert_eq!(count(&pool, "title_akas").await?, 10);
+    assert_eq!(count(&pool, "crew").await?, 7);
+    assert_eq!(count(&pool, "episodes").await?, 3);
+    assert_eq!(count(&pool, "principals").await?, 28);
+    assert_eq!(count(&pool, "ratings").await?, 7);
+    assert_eq!(count(&pool, "title_genres").await?, 22);
+    assert_eq!(count(&pool, "title_directors").await?, 6);
+    assert_eq!(count(&pool, "title_writers").await?, 9);
+    assert_eq!(count(&pool, "name_professions").await?, 37);
+
+    let directed = titles::TitleQuery::new()
+        .director("nm0000338".into())
+        .fetch(&pool)
+        .await?;
+    assert_eq!(directed.len(), 2);
+    let animated = titles::TitleQuery::new()
+        .genres(vec!["animation".into(), "Action".into()])
+        .fetch(&pool)
+        .await?;
+    assert_eq!(animated.len(), 2);
+
+    let found = titles::TitleQuery::search("parrain".into())
+        .fetch(&pool)
+        .await?;
+    assert_eq!(found[0].tconst, "tt0068646");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ingest_gzip() -> Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let dir = tempfile::tempdir()?;
+    let fixture =
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/title.ratings.tsv"))?;
+    let mut encoder = GzEncoder::new(
+        File::create(dir.path().join("title.ratings.tsv.gz"))?,
+        Compression::default(),
+    );
+    encoder.write_all(&fixture)?;
+    encoder.finish()?;
+
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    IngestClient::with_pool(pool.clone())
+        .await?
+        .data_dir(dir.path())
+        .start()
+        .await?;
+
+    assert_eq!(count(&pool, "ratings").await?, 7);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ingest_skips_bad_lines() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut dump = b"tconst\taverageRating\tnumVotes\ntt1\t7.5\t100\n".to_vec();
+    dump.extend_from_slice(b"tt2\t\xff\t50\ntt3\tn/a\t10\r\ntt4\t5.0\t10\n");
+    std::fs::write(dir.path().join("title.ratings.tsv"), dump)?;
+
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    IngestClient::with_pool(pool.clone())
+        .await?
+        .data_dir(dir.path())
+        .start()
+        .await?;
+
+    // the line that isn't UTF-8 is dropped, reading goes on after it
+    let rows: Vec<(String, Option<f64>, Option<i64>)> =
+        sqlx::query_as("SELECT tconst, average_rating, num_votes FROM ratings ORDER BY tconst")
+            .fetch_all(&pool)
+            .await?;
+    assert_eq!(
+        rows,
+        vec![
+            ("tt1".into(), Some(7.5), Some(100)),
+            ("tt3".into(), None, Some(10)),
+            ("tt4".into(), Some(5.0), Some(10)),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_truncated_gzip_keeps_rows() -> Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("title.ratings.tsv.gz");
+    let mut dump = String::from("tconst\taverageRating\tnumVotes\n");
+    for i in 0..20_000 {
+        dump.push_str(&format!("tt{i}\t{}.{}\t{i}\n", i % 10, i % 7));
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(dump.as_bytes())?;
+    let gzip = encoder.finish()?;
+    std::fs::write(&path, &gzip)?;
+
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    let client = IngestClient::with_pool(pool.clone())
+        .await?
+        .data_dir(dir.path())
+        .incremental(true);
+    client.start().await?;
+    assert_eq!(count(&pool, "ratings").await?, 20_000);
+
+    // a download cut short halfway
+    std::fs::write(&path, &gzip[..gzip.len() / 2])?;
+    assert!(client.start().await.is_err());
+    assert_eq!(count(&pool, "ratings").await?, 20_000);
+    let progress: Option<String> = sqlx::query_scalar("SELECT checksum FROM ingest_files")
+        .fetch_optional(&pool)
+        .await?;
+    assert_eq!(progress, None);
+
+    // and the complete file is read in full again
+    std::fs::write(&path, &gzip)?;
+    client.start().await?;
+    assert_eq!(count(&pool, "ratings").await?, 20_000);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_incremental_ingest() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ratings = dir.path().join("title.ratings.tsv");
+    std::fs::write(
+        &ratings,
+        "tconst\taverageRating\tnumVotes\ntt1\t7.5\t100\ntt2\t6.0\t50\n",
+    )?;
+
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?;
+    let client = IngestClient::with_pool(pool.clone())
+        .await?
+        .data_dir(dir.path())
+        .incremental(true);
+    client.start().await?;
+    assert_eq!(count(&pool, "ratings").await?, 2);
+
+    // tt1 changed, tt2 was removed and tt3 is new
+    std::fs::write(
+        &ratings,
+        "tconst\taverageRating\tnumVotes\ntt1\t7.9\t120\ntt3\t5.0\t10\n",
+    )?;
+    client.start().await?;
+
+    let rows = sqlx::query("SELECT tconst, num_votes FROM ratings ORDER BY tconst")
+        .fetch_all(&pool)
+        .await?;
+    let rows: Vec<(String, i64)> = rows
+        .iter()
+        .map(|row| (row.get("tconst"), row.get("num_votes")))
+        .collect();
+    assert_eq!(rows, vec![("tt1".into(), 120), ("tt3".into(), 10)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_tables_backfill() -> Result<()> {
+    let pool = fixture_pool().await?;
+    let lists: Vec<&ListTable> = [titles::LISTS, names::LISTS, crew::LISTS]
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut ingested = vec![];
+    for list in &lists {
+        let query = format!("SELECT * FROM {} ORDER BY 1, 2", list.table);
+        let rows: Vec<(String, String)> = sqlx::query_as(&query).fetch_all(&pool).await?;
+        assert!(!rows.is_empty(), "{} is empty", list.table);
+        ingested.push(rows);
+    }
+
+    // a database from before the list tables gets them filled from the
+    // columns they were split from
+    for list in &lists {
+        sqlx::query(&format!("DROP TABLE {}", list.table))
+            .execute(&pool)
+            .await?;
+    }
+    super::init_tables(&pool).await?;
+    for (list, ingested) in lists.iter().zip(ingested) {
+        let query = format!("SELECT * FROM {} ORDER BY 1, 2", list.table);
+        let rows: Vec<(String, String)> = sqlx::query_as(&query).fetch_all(&pool).await?;
+        assert_eq!(rows, ingested, "{}", list.table);
+    }
+    Ok(())
 }
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, Pool, Sqlite, SqlitePool};
+
+use super::{
+    search::{self, Card},
+    titles::{Page, TitleQuery},
+    DBError,
+};
+
+/// What a list is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ListKind {
+    Watchlist,
+    Seen,
+    Favorites,
+}
+
+impl ListKind {
+    /// Name given to a new list when none is picked.
+    fn default_name(self) -> &'static str {
+        match self {
+            ListKind::Watchlist => "Watchlist",
+            ListKind::Seen => "Seen",
+            ListKind::Favorites => "Favorites",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct List {
+    pub id: i64,
+    /// owner, the only one who can change the list
+    pub user_id: i64,
+    pub kind: ListKind,
+    pub name: String,
+    /// unix seconds
+    pub created_at: i64,
+    pub item_count: i64,
+}
+
+/// A title saved to a list.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ListItem {
+    pub tconst: String,
+    pub note: Option<String>,
+    /// personal rating out of 10
+    pub rating: Option<i64>,
+    /// unix seconds
+    pub added_at: i64,
+}
+
+/// A list with its items, newest first.
+#[derive(Debug, Serialize)]
+pub struct ListItems {
+    #[serde(flatten)]
+    pub list: List,
+    pub items: Vec<ListItem>,
+}
+
+/// A list item rendered as a search result card.
+#[derive(Serialize)]
+pub struct ListCard {
+    #[serde(flatten)]
+    pub card: Card,
+    pub note: Option<String>,
+    pub rating: Option<i64>,
+    pub added_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct ListCards {
+    pub list: List,
+    pub cards: Vec<ListCard>,
+}
+
+/// `lists` and `list_items` hold what users save, so unlike every other
+/// table they are never touched by ingest. Items of titles dropped from a
+/// later dump are kept, and skipped when rendering cards.
+pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS lists (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS lists_user_id ON lists (user_id)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS list_items (
+                list_id INTEGER NOT NULL,
+                tconst TEXT NOT NULL,
+                note TEXT,
+                rating INTEGER,
+                added_at INTEGER NOT NULL,
+                PRIMARY KEY (list_id, tconst)
+            )",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+const LIST: &str =
+    "SELECT l.*, (SELECT count(*) FROM list_items WHERE list_id = l.id) AS item_count
+    FROM lists AS l";
+
+/// Lists of `user_id`, oldest first.
+pub async fn all(db: &SqlitePool, user_id: i64) -> Result<Vec<List>> {
+    Ok(
+        sqlx::query_as::<_, List>(&format!("{LIST} WHERE l.user_id = ? ORDER BY l.id"))
+            .bind(user_id)
+            .fetch_all(db)
+            .await?,
+    )
+}
+
+pub async fn get(db: &SqlitePool, id: i64) -> Result<List> {
+    sqlx::query_as::<_, List>(&format!("{LIST} WHERE l.id = ?"))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(DBError::NotFound("list").into())
+}
+
+/// List `id`, failing unless `user_id` owns it. Lists are private, so this
+/// guards reading them as well as changing them.
+async fn owned(db: &SqlitePool, id: i64, user_id: i64) -> Result<List> {
+    let list = get(db, id).await?;
+    if list.user_id != user_id {
+        return Err(DBError::Forbidden("list").into());
+    }
+    Ok(list)
+}
+
+/// Creates a list for `user_id`, named after its kind unless `name` is
+/// given.
+pub async fn create(
+    db: &SqlitePool,
+    user_id: i64,
+    kind: ListKind,
+    name: Option<String>,
+) -> Result<List> {
+    let name = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(kind.default_name().into());
+    let id = sqlx::query(
+        "INSERT INTO lists (user_id, kind, name, created_at)
+            VALUES (?, ?, ?, CAST(strftime('%s', 'now') AS INTEGER))",
+    )
+    .bind(user_id)
+    .bind(kind)
+    .bind(name)
+    .execute(db)
+    .await?
+    .last_insert_rowid();
+    get(db, id).await
+}
+
+/// Deletes a list and its items.
+pub async fn delete(db: &SqlitePool, id: i64, user_id: i64) -> Result<()> {
+    owned(db, id, user_id).await?;
+    let mut transaction = db.begin().await?;
+    sqlx::query("DELETE FROM list_items WHERE list_id = ?")
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query("DELETE FROM lists WHERE id = ?")
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+pub async fn items(db: &SqlitePool, id: i64, user_id: i64) -> Result<ListItems> {
+    let list = owned(db, id, user_id).await?;
+    let items = sqlx::query_as::<_, ListItem>(
+        "SELECT tconst, note, rating, added_at FROM list_items
+            WHERE list_id = ? ORDER BY added_at DESC, rowid DESC",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+    Ok(ListItems { list, items })
+}
+
+/// Adds `tconst` to a list, or replaces the note and rating of an item
+/// already on it.
+pub async fn put_item(
+    db: &SqlitePool,
+    id: i64,
+    user_id: i64,
+    tconst: &str,
+    note: Option<String>,
+    rating: Option<i64>,
+) -> Result<ListItem> {
+    if rating.is_some_and(|rating| !(1..=10).contains(&rating)) {
+        return Err(DBError::Invalid("rating, expected 1 to 10").into());
+    }
+    owned(db, id, user_id).await?;
+    let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM titles WHERE tconst = ?")
+        .bind(tconst)
+        .fetch_optional(db)
+        .await?;
+    if exists.is_none() {
+        return Err(DBError::NotFound("title").into());
+    }
+
+    Ok(sqlx::query_as::<_, ListItem>(
+        "INSERT INTO list_items (list_id, tconst, note, rating, added_at)
+            VALUES (?, ?, ?, ?, CAST(strftime('%s', 'now') AS INTEGER))
+            ON CONFLICT (list_id, tconst) DO UPDATE SET note = excluded.note, rating = excluded.rating
+            RETURNING tconst, note, rating, added_at",
+    )
+    .bind(id)
+    .bind(tconst)
+    .bind(note.filter(|note| !note.trim().is_empty()))
+    .bind(rating)
+    .fetch_one(db)
+    .await?)
+}
+
+pub async fn remove_item(db: &SqlitePool, id: i64, user_id: i64, tconst: &str) -> Result<()> {
+    owned(db, id, user_id).await?;
+    let deleted = sqlx::query("DELETE FROM list_items WHERE list_id = ? AND tconst = ?")
+        .bind(id)
+        .bind(tconst)
+        .execute(db)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(DBError::NotFound("list item").into());
+    }
+    Ok(())
+}
+
+/// A list of `user_id` with each item as a search result card, in list
+/// order.
+pub async fn cards(db: &SqlitePool, id: i64, user_id: i64) -> Result<ListCards> {
+    let ListItems { list, items } = items(db, id, user_id).await?;
+
+    let ids: Vec<String> = items.iter().map(|item| item.tconst.clone()).collect();
+    // an empty id list would match every title
+    let mut titles: HashMap<String, _> = if ids.is_empty() {
+        HashMap::new()
+    } else {
+        TitleQuery::new()
+            .ids(&ids)
+            .fetch(db)
+            .await?
+            .into_iter()
+            .map(|title| (title.tconst.clone(), title))
+            .collect()
+    };
+    let results: Vec<_> = ids.iter().filter_map(|id| titles.remove(id)).collect();
+    let page = Page {
+        total: Some(results.len() as i64),
+        results,
+        next_cursor: None,
+    };
+    let mut cards: HashMap<String, Card> = search::cards(db, page)
+        .await?
+        .results
+        .into_iter()
+        .map(|card| (card.title.tconst.clone(), card))
+        .collect();
+
+    let cards = items
+        .into_iter()
+        .filter_map(|item| {
+            Some(ListCard {
+                card: cards.remove(&item.tconst)?,
+                note: item.note,
+                rating: item.rating,
+                added_at: item.added_at,
+            })
+        })
+        .collect();
+    Ok(ListCards { list, cards })
+}
+
+#[tokio::test]
+async fn test_lists() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+    let (owner, other) = (1, 2);
+
+    let list = create(&pool, owner, ListKind::Watchlist, None).await?;
+    assert_eq!(list.name, "Watchlist");
+    put_item(&pool, list.id, owner, "tt0068646", None, None).await?;
+    put_item(
+        &pool,
+        list.id,
+        owner,
+        "tt0317705",
+        Some("with the kids".into()),
+        None,
+    )
+    .await?;
+    // putting again updates in place
+    let item = put_item(&pool, list.id, owner, "tt0068646", None, Some(9)).await?;
+    assert_eq!(item.rating, Some(9));
+    assert_eq!(get(&pool, list.id).await?.item_count, 2);
+
+    assert!(put_item(&pool, list.id, owner, "tt0000000", None, None)
+        .await
+        .is_err());
+    assert!(put_item(&pool, list.id, owner, "tt0071562", None, Some(11))
+        .await
+        .is_err());
+    // only the owner can change it
+    assert!(put_item(&pool, list.id, other, "tt0071562", None, None)
+        .await
+        .is_err());
+    assert!(delete(&pool, list.id, other).await.is_err());
+    assert!(all(&pool, other).await?.is_empty());
+    // or see it
+    assert!(items(&pool, list.id, other).await.is_err());
+    assert!(super::lists::cards(&pool, list.id, other).await.is_err());
+
+    let cards = cards(&pool, list.id, owner).await?;
+    let titles: Vec<_> = cards.cards.iter().map(|c| &c.card.title.tconst).collect();
+    assert_eq!(titles, vec!["tt0317705", "tt0068646"]);
+    assert_eq!(cards.cards[0].note.as_deref(), Some("with the kids"));
+
+    remove_item(&pool, list.id, owner, "tt0317705").await?;
+    assert!(remove_item(&pool, list.id, owner, "tt0317705")
+        .await
+        .is_err());
+    delete(&pool, list.id, owner).await?;
+    assert!(get(&pool, list.id).await.is_err());
+    Ok(())
+}
@@ -1,38 +1,162 @@
+use sqlx::{Pool, QueryBuilder, Sqlite, Transaction};
 use std::{error::Error, fmt};
 
 const INGEST_BATCH_SIZE: usize = 100_000;
+/// Parsed batches allowed to queue up waiting for the writer.
+const INGEST_CHANNEL_SIZE: usize = 4;
+/// Rows per multi-row `INSERT`. Keeps the widest table (titles, 9 columns)
+/// well under SQLite's bound parameter limit.
+const INSERT_CHUNK_SIZE: usize = 1_000;
 
+/// Errors the query layer raises itself, as opposed to `sqlx::Error`s
+/// from the database.
 #[derive(Debug)]
-struct DBError {
-    details: String,
+pub enum DBError {
+    /// the row being looked up doesn't exist
+    NotFound(&'static str),
+    /// a filter or cursor value that can't be used
+    Invalid(&'static str),
+    /// the row being created clashes with an existing one
+    Conflict(&'static str),
+    /// the row belongs to another user
+    Forbidden(&'static str),
 }
 impl fmt::Display for DBError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+        match self {
+            DBError::NotFound(what) => write!(f, "{what} not found"),
+            DBError::Invalid(what) => write!(f, "invalid {what}"),
+            DBError::Conflict(what) => write!(f, "{what} already taken"),
+            DBError::Forbidden(what) => write!(f, "not allowed to access this {what}"),
+        }
     }
 }
 
-impl Error for DBError {
-    fn description(&self) -> &str {
-        &self.details
-    }
+impl Error for DBError {}
+
+/// A normalized `(key, value)` table holding one of the comma-joined list
+/// columns of an IMDb dump.
+struct ListTable {
+    table: &'static str,
+    key: &'static str,
+    value: &'static str,
+    /// index of the list column in the dump record
+    column: usize,
+    /// table and column the dump record is stored in, comma-joined
+    parent: (&'static str, &'static str),
 }
 
-impl DBError {
-    pub fn new(details: &str) -> Self {
-        Self {
-            details: details.to_string(),
+impl ListTable {
+    /// Creates the table, filling it from the parent's list column when the
+    /// database predates it. Incremental ingest skips unchanged files, so
+    /// nothing else would.
+    async fn init_table(&self, pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+        let ListTable {
+            table,
+            key,
+            value,
+            parent: (parent, column),
+            ..
+        } = self;
+        let mut transaction = pool.begin().await?;
+        let exists: Option<i64> =
+            sqlx::query_scalar("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?")
+                .bind(table)
+                .fetch_optional(&mut *transaction)
+                .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                {key} TEXT NOT NULL,
+                {value} TEXT NOT NULL,
+                PRIMARY KEY ({key}, {value})
+            )"
+        ))
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {table}_{value} ON {table} ({value}, {key})"
+        ))
+        .execute(&mut *transaction)
+        .await?;
+
+        if exists.is_none() {
+            sqlx::query(&format!(
+                r#"WITH RECURSIVE split (id, item, rest) AS (
+                        SELECT {key}, '', {column} || ',' FROM {parent}
+                            WHERE {column} IS NOT NULL
+                        UNION ALL
+                        SELECT id, substr(rest, 1, instr(rest, ',') - 1),
+                            substr(rest, instr(rest, ',') + 1)
+                        FROM split WHERE rest != ''
+                    )
+                    INSERT OR IGNORE INTO {table} ({key}, {value})
+                    SELECT id, item FROM split WHERE item != '' AND item != '\N'"#
+            ))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await
+    }
+
+    /// Replaces the list rows of every record in `records`.
+    async fn ingest(
+        &self,
+        transaction: &mut Transaction<'_, Sqlite>,
+        records: &[&Vec<String>],
+    ) -> Result<(), sqlx::Error> {
+        let ListTable {
+            table, key, value, ..
+        } = self;
+        for chunk in records.chunks(INSERT_CHUNK_SIZE) {
+            let mut query = QueryBuilder::new(format!("DELETE FROM {table} WHERE {key} IN ("));
+            let mut separated = query.separated(", ");
+            for record in chunk {
+                separated.push_bind(&record[0]);
+            }
+            separated.push_unseparated(")");
+            query.build().execute(&mut **transaction).await?;
+        }
+
+        let pairs: Vec<(&String, &str)> = records
+            .iter()
+            .flat_map(|record| {
+                record[self.column]
+                    .split(',')
+                    .filter(|item| !item.is_empty() && *item != "\\N")
+                    .map(|item| (&record[0], item))
+            })
+            .collect();
+        for chunk in pairs.chunks(INSERT_CHUNK_SIZE) {
+            let mut query =
+                QueryBuilder::new(format!("INSERT OR IGNORE INTO {table} ({key}, {value}) "));
+            query.push_values(chunk, |mut row, (id, item)| {
+                row.push_bind(*id).push_bind(*item);
+            });
+            query.build().execute(&mut **transaction).await?;
         }
+        Ok(())
     }
 }
 
+pub mod akas;
 pub mod client;
+pub mod collaborators;
 
 pub use client::*;
 mod crew;
 mod episodes;
+pub mod graph;
 pub mod ingest;
+pub mod lists;
 pub mod movie;
-mod names;
+pub mod names;
+pub mod person;
 mod principals;
+mod ratings;
+pub mod search;
+pub mod series;
+pub mod similar;
+pub mod suggest;
 pub mod titles;
+pub mod users;
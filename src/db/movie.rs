@@ -1,55 +1,123 @@
-use futures::future;
-
 use anyhow::Result;
 use serde::Serialize;
 use sqlx::SqlitePool;
 
 use super::{
+    akas::{self, Aka, Locale},
     crew::{self, Crew},
-    names, principals, titles,
+    episodes,
+    principals::{self, Principal},
+    titles,
 };
 
 #[derive(Serialize)]
 pub struct Movie {
     title: String,
-    year: i64,
+    /// the title in the requested locale, if it has one
+    localized_title: Option<String>,
+    year: Option<i64>,
+    average_rating: Option<f64>,
+    num_votes: Option<i64>,
     crew: Crew,
-    principals: Vec<(String, Vec<String>)>,
+    cast: Vec<Principal>,
+    series: Option<SeriesLink>,
+    akas: Vec<Aka>,
+}
+
+/// Where an episode sits in its parent series.
+#[derive(Serialize)]
+pub struct SeriesLink {
+    tconst: String,
+    /// `None` when the series is missing from the titles dump
+    title: Option<String>,
+    season_number: Option<i64>,
+    episode_number: Option<i64>,
 }
 
-pub async fn get(db: &SqlitePool, tconst: String) -> Result<Movie> {
-    let title = titles::TitleQuery::new().id(&tconst).fetch_one(&db).await?;
+/// Related rows that are missing from the dumps (ratings, crew, names, the
+/// parent series) come back as nulls or empty lists rather than errors.
+pub async fn get(db: &SqlitePool, tconst: String, locale: &Locale) -> Result<Movie> {
+    let title = titles::TitleQuery::new().id(&tconst).fetch_one(db).await?;
 
-    let crew = crew::CrewQuery::new().id(&tconst).fetch_one(&db).await?;
-    let principals = principals::PrincipalsQuery::new()
+    let crew = crew::CrewQuery::new().id(&tconst).fetch(db).await?;
+    let cast = principals::PrincipalsQuery::new()
         .movie(&tconst)
-        .fetch(&db)
+        .fetch(db)
         .await?;
-    // let director = names::NameQuery::new()
-    //     .id(&crew.directors[0])
-    //     .fetch_one(&db)
-    //     .await?;
-    let principals = future::join_all(
-        principals
-            .into_iter()
-            .map(|p| async {
-                (
-                    names::primary_name(db, p.nconst).await.unwrap(),
-                    if p.job.is_empty() {
-                        p.characters
-                    } else {
-                        vec![p.job]
-                    },
-                )
+    let series = match episodes::EpisodeQuery::new()
+        .id(&tconst)
+        .fetch_optional(db)
+        .await?
+    {
+        Some(episode) => {
+            let parent = titles::TitleQuery::new()
+                .id(&episode.parent_tconst)
+                .fetch_optional(db)
+                .await?;
+            Some(SeriesLink {
+                title: parent.map(|p| p.primary_title),
+                tconst: episode.parent_tconst,
+                season_number: episode.season_number,
+                episode_number: episode.episode_number,
             })
-            .collect::<Vec<_>>(),
-    )
-    .await;
+        }
+        None => None,
+    };
+    let akas = akas::AkaQuery::new().title(&tconst).fetch(db).await?;
+    let localized_title = akas::localized(&akas, locale).map(|aka| aka.title.clone());
 
     Ok(Movie {
         title: title.primary_title,
-        year: title.start_year,
-        principals,
+        localized_title,
+        // unknown years are stored as 0
+        year: Some(title.start_year).filter(|year| *year > 0),
+        average_rating: title.average_rating,
+        num_votes: title.num_votes,
         crew,
+        cast,
+        series,
+        akas,
     })
 }
+
+#[tokio::test]
+async fn test_movie_credits() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let movie = get(&pool, "tt0068646".into(), &Locale::default()).await?;
+    let directors: Vec<_> = movie
+        .crew
+        .directors
+        .iter()
+        .map(|d| d.name.clone())
+        .collect();
+    assert_eq!(directors, vec![Some("Francis Ford Coppola".to_string())]);
+    let first = &movie.cast[0];
+    assert_eq!(first.name.as_deref(), Some("Marlon Brando"));
+    assert_eq!(first.characters, vec!["Don Vito Corleone"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_every_title_renders() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+    let tera = tera::Tera::new("templates/**/*.html")?;
+
+    let ids: Vec<String> = sqlx::query_scalar("SELECT tconst FROM titles")
+        .fetch_all(&pool)
+        .await?;
+    assert!(!ids.is_empty());
+    for id in ids {
+        let movie = get(&pool, id.clone(), &Locale::default()).await?;
+        let context = tera::Context::from_serialize(&movie)?;
+        tera.render("movie.html", &context)
+            .map_err(|e| anyhow::anyhow!("{id}: {e:?}"))?;
+    }
+
+    // a crew member and a principal missing from names, and no crew row
+    let movie = get(&pool, "tt1054724".into(), &Locale::default()).await?;
+    assert_eq!(movie.crew.directors[0].name, None);
+    let movie = get(&pool, "tt1232244".into(), &Locale::default()).await?;
+    assert!(movie.crew.directors.is_empty() && movie.cast.is_empty());
+    Ok(())
+}
@@ -1,6 +1,10 @@
 use anyhow::Result;
 use serde::Serialize;
-use sqlx::{prelude::FromRow, sqlite::SqliteRow, Pool, Row, Sqlite, SqlitePool, Transaction};
+use sqlx::{
+    prelude::FromRow, sqlite::SqliteRow, Pool, QueryBuilder, Row, Sqlite, SqlitePool, Transaction,
+};
+
+use super::ListTable;
 
 #[derive(Debug, Serialize)]
 pub struct Name {
@@ -20,7 +24,7 @@ impl<'r> FromRow<'r, SqliteRow> for Name {
         let known_for_titles = titles_str.split(',').map(|s| s.to_owned()).collect();
 
         Ok(Self {
-            nconst: row.try_get("tconst").unwrap_or("".into()),
+            nconst: row.try_get("nconst").unwrap_or("".into()),
             primary_name: row.try_get("primary_name").unwrap_or("".into()),
             birth_year: row.try_get("birth_year").unwrap_or(None),
             death_year: row.try_get("death_year").unwrap_or(None),
@@ -30,6 +34,23 @@ impl<'r> FromRow<'r, SqliteRow> for Name {
     }
 }
 
+pub(super) const LISTS: &[ListTable] = &[
+    ListTable {
+        table: "name_professions",
+        key: "nconst",
+        value: "profession",
+        column: 4,
+        parent: ("names", "primary_profession"),
+    },
+    ListTable {
+        table: "name_known_for",
+        key: "nconst",
+        value: "tconst",
+        column: 5,
+        parent: ("names", "known_for_titles"),
+    },
+];
+
 pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     // Create all necessary tables
     sqlx::query(
@@ -44,84 +65,164 @@ pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     )
     .execute(pool)
     .await?;
+
+    for list in LISTS {
+        list.init_table(pool).await?;
+    }
     Ok(())
 }
 
-pub async fn primary_name(db: &SqlitePool, id: String) -> Result<String> {
-    // TODO check if there is anything else in query and error
-    if id.is_empty() {
-        return Err(super::DBError::new("empty id".into()).into());
-    }
-    let name = sqlx::query_as::<_, Name>("SELECT * FROM names WHERE nconst = ?")
-        .bind(id)
-        .fetch_one(db)
-        .await?;
-    Ok(name.primary_name)
+pub struct NameQuery<'a> {
+    query: QueryBuilder<'a, Sqlite>,
+    filtered: bool,
 }
 
-// pub struct NameQuery<'a>(QueryBuilder<'a, Sqlite>);
-// impl<'a> NameQuery<'a> {
-//     pub fn new() -> Self {
-//         NameQuery(QueryBuilder::new("SELECT * FROM names"))
-//     }
-
-//     pub fn id(mut self, id: &'a String) -> Self {
-//         if !id.is_empty() {
-//             self.where_and();
-//             self.0.push(" nconst = ");
-//             self.0.push_bind(id);
-//         }
-//         self
-//     }
-
-//     fn where_and(&mut self) {
-//         if !self.0.sql().contains("WHERE") {
-//             self.0.push(" WHERE");
-//         } else {
-//             self.0.push(" AND");
-//         }
-//     }
-
-//     fn known_for_titles(mut self, tconst: &'a String) -> Self {
-//         if !tconst.is_empty() {
-//             self.where_and();
-//             self.0.push(" known_for_titles CONTAINS ");
-//             self.0.push_bind(tconst);
-//         }
-//         self
-//     }
-
-//     pub async fn fetch_one(mut self, db: &SqlitePool) -> Result<Name> {
-//         Ok(self.0.build_query_as::<Name>().fetch_one(db).await?)
-//     }
-// }
+impl<'a> NameQuery<'a> {
+    pub fn new() -> Self {
+        NameQuery {
+            query: QueryBuilder::new("SELECT * FROM names"),
+            filtered: false,
+        }
+    }
+
+    pub fn id(mut self, id: &'a String) -> Self {
+        if !id.is_empty() {
+            self.where_and();
+            self.query.push(" nconst = ");
+            self.query.push_bind(id);
+        }
+        self
+    }
+
+    pub fn ids(mut self, ids: &'a [String]) -> Self {
+        if !ids.is_empty() {
+            self.where_and();
+            self.query.push(" nconst IN (");
+            let mut separated = self.query.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            separated.push_unseparated(")");
+        }
+        self
+    }
+
+    pub fn like(mut self, name: String) -> Self {
+        if !name.is_empty() {
+            self.where_and();
+            self.query.push(" primary_name LIKE ");
+            self.query.push_bind(format!("{}%", name));
+            self.query.push(" COLLATE NOCASE ");
+        }
+        self
+    }
+
+    pub fn profession(mut self, profession: String) -> Self {
+        if !profession.is_empty() {
+            self.where_and();
+            self.query.push(
+                " EXISTS (SELECT 1 FROM name_professions AS p WHERE p.nconst = names.nconst AND p.profession = ",
+            );
+            self.query.push_bind(profession);
+            self.query.push(")");
+        }
+        self
+    }
+
+    pub fn limit(mut self, number: i64) -> Self {
+        self.query.push(" LIMIT ");
+        self.query.push_bind(number);
+        self
+    }
+
+    fn where_and(&mut self) {
+        if !self.filtered {
+            self.query.push(" WHERE");
+            self.filtered = true;
+        } else {
+            self.query.push(" AND");
+        }
+    }
+
+    pub async fn fetch_one(mut self, db: &SqlitePool) -> Result<Name> {
+        self.query
+            .build_query_as::<Name>()
+            .fetch_optional(db)
+            .await?
+            .ok_or(super::DBError::NotFound("name").into())
+    }
+    pub async fn fetch(mut self, db: &SqlitePool) -> Result<Vec<Name>> {
+        Ok(self.query.build_query_as::<Name>().fetch_all(db).await?)
+    }
+}
 
 pub async fn ingest(
     transaction: &mut Transaction<'_, Sqlite>,
-    record: &Vec<String>,
+    batch: &[Vec<String>],
 ) -> Result<(), sqlx::Error> {
-    if record.len() >= 6 {
-        sqlx::query(
-            "INSERT OR IGNORE INTO names 
-            (nconst, primary_name, birth_year, death_year, primary_profession, known_for_titles)
-            VALUES (?, ?, ?, ?, ?, ?)",
-        )
-        .bind(&record[0])
-        .bind(&record[1])
-        .bind(if record[2] == "\\N" {
-            None
-        } else {
-            record[2].parse::<i32>().ok()
-        })
-        .bind(if record[3] == "\\N" {
-            None
-        } else {
-            record[3].parse::<i32>().ok()
-        })
-        .bind(&record[4])
-        .bind(&record[5])
-        .execute(&mut **transaction)
-        .await?;
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 6).collect();
+    for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            "INSERT OR REPLACE INTO names
+            (nconst, primary_name, birth_year, death_year, primary_profession, known_for_titles) ",
+        );
+        query.push_values(chunk, |mut row, record| {
+            row.push_bind(&record[0])
+                .push_bind(&record[1])
+                .push_bind(if record[2] == "\\N" {
+                    None
+                } else {
+                    record[2].parse::<i32>().ok()
+                })
+                .push_bind(if record[3] == "\\N" {
+                    None
+                } else {
+                    record[3].parse::<i32>().ok()
+                })
+                .push_bind(&record[4])
+                .push_bind(&record[5]);
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    for list in LISTS {
+        list.ingest(transaction, &records).await?;
     }
     Ok(())
 }
+
+#[tokio::test]
+async fn test_name_query() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+    let nconsts = |names: Vec<Name>| names.into_iter().map(|n| n.nconst).collect::<Vec<_>>();
+
+    let coppola = NameQuery::new()
+        .id(&"nm0000338".into())
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(coppola.primary_name, "Francis Ford Coppola");
+    assert_eq!(coppola.death_year, None);
+    assert!(NameQuery::new()
+        .id(&"nm0000000".into())
+        .fetch_one(&pool)
+        .await
+        .is_err());
+
+    // prefix match, any case
+    let b = NameQuery::new().like("b".into()).fetch(&pool).await?;
+    assert_eq!(nconsts(b), vec!["nm0083348", "nm0186505"]);
+
+    let composers = NameQuery::new()
+        .profession("composer".into())
+        .fetch(&pool)
+        .await?;
+    assert_eq!(nconsts(composers), vec!["nm0000025"]);
+
+    let writing_actors = NameQuery::new()
+        .like("m".into())
+        .profession("writer".into())
+        .limit(10)
+        .fetch(&pool)
+        .await?;
+    assert_eq!(nconsts(writing_actors), vec!["nm0000008", "nm0701374"]);
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, sqlite::SqliteRow, Row, SqlitePool};
+
+use super::{
+    names, principals,
+    titles::{self, Title},
+};
+
+#[derive(Serialize)]
+pub struct Person {
+    nconst: String,
+    name: String,
+    birth_year: Option<i32>,
+    death_year: Option<i32>,
+    professions: Vec<String>,
+    known_for: Vec<Title>,
+    filmography: BTreeMap<String, Vec<Credit>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Credit {
+    tconst: String,
+    title: String,
+    title_type: String,
+    year: i64,
+    job: String,
+    characters: Vec<String>,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Credit {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let characters = principals::parse_characters(
+            &row.try_get::<String, _>("characters").unwrap_or_default(),
+        );
+
+        Ok(Self {
+            tconst: row.try_get("tconst").unwrap_or("".into()),
+            title: row.try_get("primary_title").unwrap_or("".into()),
+            title_type: row.try_get("title_type").unwrap_or("".into()),
+            year: row.try_get("start_year").unwrap_or(0),
+            job: row
+                .try_get::<String, _>("job")
+                .ok()
+                .filter(|job| job != "\\N")
+                .unwrap_or_default(),
+            characters,
+        })
+    }
+}
+
+pub async fn get(db: &SqlitePool, nconst: String) -> Result<Person> {
+    let name = names::NameQuery::new().id(&nconst).fetch_one(db).await?;
+
+    let known_for_ids: Vec<String> = name
+        .known_for_titles
+        .into_iter()
+        .filter(|t| !t.is_empty() && t != "\\N")
+        .collect();
+    let known_for = if known_for_ids.is_empty() {
+        vec![]
+    } else {
+        titles::TitleQuery::new()
+            .ids(&known_for_ids)
+            .fetch(db)
+            .await?
+    };
+
+    let credits = sqlx::query(
+        r#"SELECT p.category, p.job, p.characters,
+                t.tconst, t.primary_title, t.title_type, t.start_year
+            FROM principals AS p
+            JOIN titles AS t ON t.tconst = p.tconst
+            WHERE p.nconst = ?
+            ORDER BY t.start_year DESC, p.ordering"#,
+    )
+    .bind(&nconst)
+    .fetch_all(db)
+    .await?;
+
+    let mut filmography: BTreeMap<String, Vec<Credit>> = BTreeMap::new();
+    for row in credits {
+        let category: String = row.try_get("category").unwrap_or("".into());
+        filmography
+            .entry(category)
+            .or_default()
+            .push(Credit::from_row(&row)?);
+    }
+
+    Ok(Person {
+        nconst: name.nconst,
+        name: name.primary_name,
+        birth_year: name.birth_year,
+        death_year: name.death_year,
+        professions: name
+            .primary_profession
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect(),
+        known_for,
+        filmography,
+    })
+}
+
+#[tokio::test]
+async fn test_person() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let bird = get(&pool, "nm0083348".into()).await?;
+    assert_eq!(bird.professions, vec!["writer", "director", "actor"]);
+    // only known for titles in the dump are resolved
+    let known_for: Vec<_> = bird.known_for.iter().map(|t| t.tconst.as_str()).collect();
+    assert_eq!(known_for, vec!["tt0317705"]);
+
+    // credits are grouped by category, newest first
+    let credits = |category: &str| {
+        bird.filmography[category]
+            .iter()
+            .map(|c| c.tconst.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(bird.filmography.len(), 2);
+    assert_eq!(credits("director"), vec!["tt3606756", "tt0317705"]);
+    assert_eq!(credits("actor"), vec!["tt0317705"]);
+
+    assert!(get(&pool, "nm0000000".into()).await.is_err());
+    Ok(())
+}
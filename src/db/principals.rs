@@ -9,9 +9,10 @@ pub struct Principal {
     pub tconst: String,
     pub ordering: i64,
     pub nconst: String,
+    /// `None` when the person is missing from the names dump
+    pub name: Option<String>,
     pub category: String,
     pub job: String,
-    // TODO: next ingest make this column an array
     pub characters: Vec<String>,
 }
 
@@ -23,28 +24,44 @@ pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
                 nconst TEXT,
                 category TEXT,
                 job TEXT,
+                -- JSON array of strings, `[]` when there are none
                 characters TEXT,
                 PRIMARY KEY (tconst, ordering)
             )",
     )
     .execute(pool)
     .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS principals_nconst ON principals (nconst)")
+        .execute(pool)
+        .await?;
     Ok(())
 }
 
+/// Decodes the JSON array of a `characters` column. `\N` and anything that
+/// isn't an array of strings give no characters.
+pub(super) fn parse_characters(raw: &str) -> Vec<String> {
+    serde_json::from_str(raw).unwrap_or_default()
+}
+
 pub struct PrincipalsQuery<'a>(QueryBuilder<'a, Sqlite>);
 
 impl<'r> FromRow<'r, SqliteRow> for Principal {
     fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
-        let characters_str = row.try_get("writers").unwrap_or("".to_owned());
-        let characters = characters_str.split(',').map(|s| s.to_owned()).collect();
+        let characters =
+            parse_characters(&row.try_get::<String, _>("characters").unwrap_or_default());
 
         Ok(Self {
             tconst: row.try_get("tconst").unwrap_or("".into()),
             ordering: row.try_get("ordering").unwrap_or(0),
             nconst: row.try_get("nconst").unwrap_or("".into()),
+            name: row.try_get("primary_name").unwrap_or(None),
             category: row.try_get("category").unwrap_or("".into()),
-            job: row.try_get("job").unwrap_or("".into()),
+            job: row
+                .try_get::<String, _>("job")
+                .ok()
+                .filter(|job| job != "\\N")
+                .unwrap_or_default(),
             characters,
         })
     }
@@ -52,13 +69,16 @@ impl<'r> FromRow<'r, SqliteRow> for Principal {
 
 impl<'a> PrincipalsQuery<'a> {
     pub fn new() -> Self {
-        PrincipalsQuery(QueryBuilder::new("SELECT * FROM principals"))
+        PrincipalsQuery(QueryBuilder::new(
+            r#"SELECT p.*, n.primary_name FROM principals AS p
+                LEFT JOIN names AS n ON n.nconst = p.nconst"#,
+        ))
     }
 
     pub fn movie(mut self, id: &'a String) -> Self {
         if !id.is_empty() {
             self.where_and();
-            self.0.push(" tconst = ");
+            self.0.push(" p.tconst = ");
             self.0.push_bind(id);
         }
         self
@@ -76,29 +96,74 @@ impl<'a> PrincipalsQuery<'a> {
     //     Ok(self.0.build_query_as::<Principal>().fetch_one(db).await?)
     // }
     pub async fn fetch(mut self, db: &SqlitePool) -> Result<Vec<Principal>> {
+        self.0.push(" ORDER BY p.ordering");
         Ok(self.0.build_query_as::<Principal>().fetch_all(db).await?)
     }
 }
 
 pub async fn ingest(
     transaction: &mut Transaction<'_, Sqlite>,
-    record: &Vec<String>,
+    batch: &[Vec<String>],
 ) -> Result<(), sqlx::Error> {
-    if record.len() >= 6 {
-        sqlx::query(
-            r#"INSERT OR IGNORE INTO principals 
-                (tconst, ordering, nconst, category, job, characters)
-                VALUES (?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&record[0])
-        .bind(record[1].parse::<i32>().unwrap_or(0))
-        .bind(&record[2])
-        .bind(&record[3])
-        .bind(&record[4])
-        .bind(&record[5])
-        .execute(&mut **transaction)
-        .await?;
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 6).collect();
+    // re-encoded so the column is always a valid array for SQLite's JSON functions
+    let characters: Vec<String> = records
+        .iter()
+        .map(|r| serde_json::to_string(&parse_characters(&r[5])).unwrap_or("[]".into()))
+        .collect();
+    for (chunk, characters) in records
+        .chunks(super::INSERT_CHUNK_SIZE)
+        .zip(characters.chunks(super::INSERT_CHUNK_SIZE))
+    {
+        let mut query = QueryBuilder::new(
+            "INSERT OR REPLACE INTO principals
+                (tconst, ordering, nconst, category, job, characters) ",
+        );
+        query.push_values(
+            chunk.iter().zip(characters),
+            |mut row, (record, characters)| {
+                row.push_bind(&record[0])
+                    .push_bind(record[1].parse::<i32>().unwrap_or(0))
+                    .push_bind(&record[2])
+                    .push_bind(&record[3])
+                    .push_bind(&record[4])
+                    .push_bind(characters);
+            },
+        );
+        query.build().execute(&mut **transaction).await?;
     }
     Ok(())
 }
+
+#[tokio::test]
+async fn test_characters() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let id = "tt0317705".to_string();
+    let principals = PrincipalsQuery::new().movie(&id).fetch(&pool).await?;
+    let edna = principals.iter().find(|p| p.ordering == 4).unwrap();
+    assert_eq!(edna.characters, vec![r#"Edna "E" Mode"#]);
+    let bob = &principals[0];
+    assert_eq!(bob.characters, vec!["Bob Parr", "Mr. Incredible"]);
+
+    // a comma inside a name stays one character
+    let (tconst, character): (String, String) = sqlx::query_as(
+        r#"SELECT p.tconst, c.value FROM principals AS p, json_each(p.characters) AS c
+            WHERE c.value LIKE 'Bob Parr%' AND p.tconst = 'tt3606756'"#,
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(
+        (tconst.as_str(), character.as_str()),
+        ("tt3606756", "Bob Parr, aka Mr. Incredible")
+    );
+
+    // no characters is an empty array rather than `\N`
+    let empty: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM principals WHERE json_array_length(characters) = 0",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert!(empty > 0);
+    Ok(())
+}
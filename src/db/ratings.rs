@@ -0,0 +1,39 @@
+use sqlx::{Pool, QueryBuilder, Sqlite, Transaction};
+
+pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS ratings (
+                tconst TEXT PRIMARY KEY,
+                average_rating REAL,
+                num_votes INTEGER
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS ratings_num_votes ON ratings (num_votes)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn ingest(
+    transaction: &mut Transaction<'_, Sqlite>,
+    batch: &[Vec<String>],
+) -> Result<(), sqlx::Error> {
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 3).collect();
+    for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            "INSERT OR REPLACE INTO ratings (tconst, average_rating, num_votes) ",
+        );
+        query.push_values(chunk, |mut row, record| {
+            // NULL rather than a made up 0 when a field doesn't parse
+            row.push_bind(&record[0])
+                .push_bind(record[1].parse::<f64>().ok())
+                .push_bind(record[2].parse::<i64>().ok());
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    Ok(())
+}
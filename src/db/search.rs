@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{FromRow, QueryBuilder, Row, Sqlite, SqlitePool};
+
+use super::{
+    crew::CrewMember,
+    principals::Principal,
+    titles::{Page, Title},
+};
+
+/// Billed cast shown on a result card.
+const TOP_BILLED: i64 = 3;
+
+/// A search result with the people shown alongside it.
+#[derive(Serialize)]
+pub struct Card {
+    #[serde(flatten)]
+    pub title: Title,
+    pub directors: Vec<CrewMember>,
+    /// first billed actors, actresses and self appearances
+    pub cast: Vec<Principal>,
+}
+
+#[derive(Serialize)]
+pub struct Cards {
+    pub results: Vec<Card>,
+    pub next_cursor: Option<String>,
+    pub total: Option<i64>,
+}
+
+fn push_ids(query: &mut QueryBuilder<'_, Sqlite>, ids: &[String]) {
+    query.push(" (");
+    let mut separated = query.separated(", ");
+    for id in ids {
+        separated.push_bind(id.clone());
+    }
+    separated.push_unseparated(")");
+}
+
+/// Looks up directors and top billed cast for a page of titles, one query
+/// each for the whole page.
+pub async fn cards(db: &SqlitePool, page: Page) -> Result<Cards> {
+    let ids: Vec<String> = page.results.iter().map(|t| t.tconst.clone()).collect();
+    let mut directors: HashMap<String, Vec<CrewMember>> = HashMap::new();
+    let mut cast: HashMap<String, Vec<Principal>> = HashMap::new();
+
+    if !ids.is_empty() {
+        let mut query = QueryBuilder::new(
+            r#"SELECT l.tconst, l.nconst, n.primary_name FROM title_directors AS l
+                LEFT JOIN names AS n ON n.nconst = l.nconst
+                WHERE l.tconst IN"#,
+        );
+        push_ids(&mut query, &ids);
+        query.push(" ORDER BY l.rowid");
+        for row in query.build().fetch_all(db).await? {
+            directors
+                .entry(row.try_get("tconst")?)
+                .or_default()
+                .push(CrewMember::from_row(&row)?);
+        }
+
+        let mut query = QueryBuilder::new(
+            r#"SELECT * FROM (
+                SELECT p.*, n.primary_name,
+                    row_number() OVER (PARTITION BY p.tconst ORDER BY p.ordering) AS billing
+                FROM principals AS p
+                LEFT JOIN names AS n ON n.nconst = p.nconst
+                WHERE p.category IN ('actor', 'actress', 'self') AND p.tconst IN"#,
+        );
+        push_ids(&mut query, &ids);
+        query.push(") WHERE billing <= ");
+        query.push_bind(TOP_BILLED);
+        query.push(" ORDER BY billing");
+        for row in query.build().fetch_all(db).await? {
+            let principal = Principal::from_row(&row)?;
+            cast.entry(principal.tconst.clone())
+                .or_default()
+                .push(principal);
+        }
+    }
+
+    let results = page
+        .results
+        .into_iter()
+        .map(|title| Card {
+            directors: directors.remove(&title.tconst).unwrap_or_default(),
+            cast: cast.remove(&title.tconst).unwrap_or_default(),
+            title,
+        })
+        .collect();
+    Ok(Cards {
+        results,
+        next_cursor: page.next_cursor,
+        total: page.total,
+    })
+}
+
+#[tokio::test]
+async fn test_cards() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let page = super::titles::TitleQuery::search("incredibles".into())
+        .fetch_page(&pool)
+        .await?;
+    let cards = cards(&pool, page).await?;
+    let first = cards
+        .results
+        .iter()
+        .find(|c| c.title.tconst == "tt0317705")
+        .unwrap();
+    assert_eq!(first.directors[0].name.as_deref(), Some("Brad Bird"));
+    let cast: Vec<_> = first.cast.iter().map(|p| p.ordering).collect();
+    assert_eq!(cast, vec![1, 2, 3]);
+    Ok(())
+}
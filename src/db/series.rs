@@ -0,0 +1,75 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use super::{
+    episodes::{self, Episode},
+    titles,
+};
+
+#[derive(Serialize)]
+pub struct Series {
+    tconst: String,
+    title: String,
+    start_year: i64,
+    end_year: i64,
+    seasons: Vec<Season>,
+}
+
+#[derive(Serialize)]
+pub struct Season {
+    number: Option<i64>,
+    episodes: Vec<Episode>,
+}
+
+pub async fn get(db: &SqlitePool, tconst: String) -> Result<Series> {
+    let title = titles::TitleQuery::new().id(&tconst).fetch_one(db).await?;
+    let episodes = episodes::EpisodeQuery::new()
+        .series(&tconst)
+        .fetch(db)
+        .await?;
+
+    // episodes come back ordered by season, so consecutive runs are seasons
+    let mut seasons: Vec<Season> = vec![];
+    for episode in episodes {
+        match seasons.last_mut() {
+            Some(season) if season.number == episode.season_number => season.episodes.push(episode),
+            _ => seasons.push(Season {
+                number: episode.season_number,
+                episodes: vec![episode],
+            }),
+        }
+    }
+
+    Ok(Series {
+        tconst: title.tconst,
+        title: title.primary_title,
+        start_year: title.start_year,
+        end_year: title.end_year,
+        seasons,
+    })
+}
+
+#[tokio::test]
+async fn test_series() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let series = get(&pool, "tt0903747".into()).await?;
+    assert_eq!(series.title, "Breaking Bad");
+    let seasons: Vec<_> = series
+        .seasons
+        .iter()
+        .map(|s| {
+            let episodes: Vec<_> = s.episodes.iter().map(|e| e.episode_number).collect();
+            (s.number, episodes)
+        })
+        .collect();
+    assert_eq!(
+        seasons,
+        vec![(Some(1), vec![Some(1), Some(2)]), (Some(2), vec![Some(1)])]
+    );
+
+    // a title without episodes has no seasons
+    assert!(get(&pool, "tt0068646".into()).await?.seasons.is_empty());
+    Ok(())
+}
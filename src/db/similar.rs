@@ -0,0 +1,319 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, sqlite::SqliteRow, Pool, QueryBuilder, Row, Sqlite, SqlitePool};
+
+use super::{collaborators::CAST, DBError};
+
+/// Most voted titles sharing a genre considered as candidates, on top of
+/// every title sharing a person.
+const GENRE_CANDIDATES: i64 = 200;
+/// Years apart at which era proximity stops counting.
+const ERA_SPAN: f64 = 30.0;
+
+/// Weights of each part of the score.
+const GENRE_WEIGHT: f64 = 3.0;
+const DIRECTOR_WEIGHT: f64 = 2.0;
+const WRITER_WEIGHT: f64 = 1.0;
+const CAST_WEIGHT: f64 = 0.75;
+const ERA_WEIGHT: f64 = 1.0;
+const RATING_WEIGHT: f64 = 1.0;
+
+/// A recommended title and why it was picked.
+#[derive(Debug, Serialize)]
+pub struct Recommendation {
+    pub tconst: String,
+    pub title: String,
+    pub title_type: String,
+    pub year: Option<i64>,
+    pub average_rating: Option<f64>,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+/// A row of `title_features`.
+struct Features {
+    tconst: String,
+    title_type: String,
+    primary_title: String,
+    year: Option<i64>,
+    genres: Vec<String>,
+    genre_mask: i64,
+    average_rating: Option<f64>,
+    num_votes: i64,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Features {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let genres: String = row.try_get("genres").unwrap_or_default();
+        Ok(Self {
+            tconst: row.try_get("tconst").unwrap_or("".into()),
+            title_type: row.try_get("title_type").unwrap_or("".into()),
+            primary_title: row.try_get("primary_title").unwrap_or("".into()),
+            year: row
+                .try_get::<Option<i64>, _>("year")
+                .unwrap_or(None)
+                .filter(|year| *year > 0),
+            genres: genres
+                .split(',')
+                .filter(|g| !g.is_empty())
+                .map(String::from)
+                .collect(),
+            genre_mask: row.try_get("genre_mask").unwrap_or(0),
+            average_rating: row.try_get("average_rating").unwrap_or(None),
+            num_votes: row.try_get("num_votes").unwrap_or(0),
+        })
+    }
+}
+
+/// People a candidate shares with the title, by role.
+#[derive(Default)]
+struct Shared {
+    directors: Vec<String>,
+    writers: Vec<String>,
+    cast: Vec<String>,
+}
+
+/// `title_features` flattens what recommendations are scored on, one row
+/// per title, with the genres also as a bitmask so candidates sharing one
+/// can be found without joining `title_genres`.
+pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS title_features (
+                tconst TEXT PRIMARY KEY,
+                title_type TEXT,
+                primary_title TEXT,
+                is_adult INTEGER,
+                year INTEGER,
+                genres TEXT,
+                genre_mask INTEGER NOT NULL,
+                average_rating REAL,
+                num_votes INTEGER NOT NULL
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS title_features_votes ON title_features (num_votes)")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Rebuilds `title_features` from titles, genres and ratings. Run after
+/// ingest.
+pub async fn build_features(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM title_features")
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(
+        r#"WITH bits AS (
+                SELECT genre, row_number() OVER (ORDER BY genre) - 1 AS bit
+                FROM (SELECT DISTINCT genre FROM title_genres)
+            ),
+            genres AS (
+                SELECT g.tconst, group_concat(g.genre) AS genres,
+                    sum(CASE WHEN b.bit < 63 THEN 1 << b.bit ELSE 0 END) AS mask
+                FROM title_genres AS g
+                JOIN bits AS b ON b.genre = g.genre
+                GROUP BY g.tconst
+            )
+            INSERT INTO title_features
+                (tconst, title_type, primary_title, is_adult, year, genres, genre_mask, average_rating, num_votes)
+            SELECT t.tconst, t.title_type, t.primary_title, t.is_adult, t.start_year,
+                g.genres, COALESCE(g.mask, 0), r.average_rating, COALESCE(r.num_votes, 0)
+            FROM titles AS t
+            LEFT JOIN genres AS g ON g.tconst = t.tconst
+            LEFT JOIN ratings AS r ON r.tconst = t.tconst"#,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Candidate columns of `title_features`; episodes and adult titles are
+/// never recommended.
+const CANDIDATE: &str = "SELECT * FROM title_features
+    WHERE title_type != 'tvEpisode' AND is_adult = 0";
+
+/// Titles sharing people with `tconst`, and who they share.
+async fn shared_people(db: &SqlitePool, tconst: &str) -> Result<HashMap<String, Shared>> {
+    let rows = sqlx::query(&format!(
+        r#"SELECT s.tconst, s.role, COALESCE(n.primary_name, s.nconst) AS name FROM (
+                SELECT d.tconst, 'director' AS role, d.nconst FROM title_directors AS d
+                    WHERE d.nconst IN (SELECT nconst FROM title_directors WHERE tconst = ?1)
+                UNION SELECT w.tconst, 'writer', w.nconst FROM title_writers AS w
+                    WHERE w.nconst IN (SELECT nconst FROM title_writers WHERE tconst = ?1)
+                UNION SELECT p.tconst, 'cast', p.nconst FROM principals AS p
+                    WHERE p.category IN {CAST} AND p.nconst IN (
+                        SELECT nconst FROM principals WHERE tconst = ?1 AND category IN {CAST}
+                    )
+            ) AS s
+            LEFT JOIN names AS n ON n.nconst = s.nconst
+            WHERE s.tconst != ?1"#
+    ))
+    .bind(tconst)
+    .fetch_all(db)
+    .await?;
+
+    let mut shared: HashMap<String, Shared> = HashMap::new();
+    for row in rows {
+        let entry = shared.entry(row.try_get("tconst")?).or_default();
+        let name: String = row.try_get("name")?;
+        match row.try_get::<&str, _>("role")? {
+            "director" => entry.directors.push(name),
+            "writer" => entry.writers.push(name),
+            _ => entry.cast.push(name),
+        }
+    }
+    Ok(shared)
+}
+
+/// "A", "A and B", "A, B and C", then "A, B and 3 others".
+fn describe(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] if names.len() <= 3 => format!("{} and {last}", rest.join(", ")),
+        _ => format!("{} and {} others", names[..2].join(", "), names.len() - 2),
+    }
+}
+
+fn score(source: &Features, candidate: &Features, shared: Option<&Shared>) -> (f64, Vec<String>) {
+    let mut score = 0.0;
+    let mut reasons = vec![];
+
+    let genres: HashSet<&String> = source.genres.iter().collect();
+    let common: Vec<String> = candidate
+        .genres
+        .iter()
+        .filter(|g| genres.contains(g))
+        .cloned()
+        .collect();
+    if !common.is_empty() {
+        let union = genres.len() + candidate.genres.len() - common.len();
+        score += GENRE_WEIGHT * common.len() as f64 / union as f64;
+        reasons.push(format!("shares {}", common.join(", ")));
+    }
+
+    if let Some(shared) = shared {
+        if !shared.directors.is_empty() {
+            score += DIRECTOR_WEIGHT * shared.directors.len().min(2) as f64;
+            reasons.push(format!("directed by {}", describe(&shared.directors)));
+        }
+        if !shared.writers.is_empty() {
+            score += WRITER_WEIGHT * shared.writers.len().min(2) as f64;
+            reasons.push(format!("written by {}", describe(&shared.writers)));
+        }
+        if !shared.cast.is_empty() {
+            score += CAST_WEIGHT * shared.cast.len().min(4) as f64;
+            reasons.push(format!("with {}", describe(&shared.cast)));
+        }
+    }
+
+    if let (Some(year), Some(other)) = (source.year, candidate.year) {
+        let proximity = 1.0 - ((year - other).abs() as f64 / ERA_SPAN).min(1.0);
+        score += ERA_WEIGHT * proximity;
+        if (year - other).abs() <= 5 {
+            reasons.push(format!("from the same era ({other})"));
+        }
+    }
+
+    if let Some(rating) = candidate.average_rating {
+        // a handful of votes says little about the rating
+        let confidence = ((candidate.num_votes.max(1) as f64).log10() / 5.0).min(1.0);
+        score += RATING_WEIGHT * rating / 10.0 * confidence;
+        if rating >= 7.5 && confidence >= 0.6 {
+            reasons.push(format!("rated {rating:.1}"));
+        }
+    }
+
+    (score, reasons)
+}
+
+/// Scores titles sharing people or genres with `tconst` and returns the
+/// best `limit`.
+pub async fn similar(db: &SqlitePool, tconst: &str, limit: i64) -> Result<Vec<Recommendation>> {
+    let source = sqlx::query_as::<_, Features>("SELECT * FROM title_features WHERE tconst = ?")
+        .bind(tconst)
+        .fetch_optional(db)
+        .await?
+        .ok_or(DBError::NotFound("title"))?;
+
+    let shared = shared_people(db, tconst).await?;
+
+    let mut candidates: HashMap<String, Features> = HashMap::new();
+    if source.genre_mask != 0 {
+        let mut query = QueryBuilder::<Sqlite>::new(CANDIDATE);
+        query.push(" AND genre_mask & ");
+        query.push_bind(source.genre_mask);
+        query.push(" != 0 AND tconst != ");
+        query.push_bind(tconst);
+        query.push(" ORDER BY num_votes DESC LIMIT ");
+        query.push_bind(GENRE_CANDIDATES);
+        for features in query.build_query_as::<Features>().fetch_all(db).await? {
+            candidates.insert(features.tconst.clone(), features);
+        }
+    }
+    let ids: Vec<&String> = shared.keys().collect();
+    for chunk in ids.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Sqlite>::new(CANDIDATE);
+        query.push(" AND tconst IN (");
+        let mut separated = query.separated(", ");
+        for id in chunk {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        for features in query.build_query_as::<Features>().fetch_all(db).await? {
+            candidates.insert(features.tconst.clone(), features);
+        }
+    }
+
+    let mut recommendations: Vec<Recommendation> = candidates
+        .into_values()
+        .map(|candidate| {
+            let (score, reasons) = score(&source, &candidate, shared.get(&candidate.tconst));
+            Recommendation {
+                tconst: candidate.tconst,
+                title: candidate.primary_title,
+                title_type: candidate.title_type,
+                year: candidate.year,
+                average_rating: candidate.average_rating,
+                score: (score * 1000.0).round() / 1000.0,
+                reasons,
+            }
+        })
+        .collect();
+    recommendations.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.tconst.cmp(&b.tconst))
+    });
+    recommendations.truncate(limit.max(0) as usize);
+    Ok(recommendations)
+}
+
+#[tokio::test]
+async fn test_similar() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let similar = similar(&pool, "tt0068646", 10).await?;
+    // the sequel shares genres, director, writers and cast
+    assert_eq!(similar[0].tconst, "tt0071562");
+    assert!(similar[0]
+        .reasons
+        .iter()
+        .any(|r| r == "directed by Francis Ford Coppola"));
+    // Breaking Bad only shares genres
+    assert!(similar.iter().any(|r| r.tconst == "tt0903747"));
+    // and episodes are left out
+    assert!(similar.iter().all(|r| r.title_type != "tvEpisode"));
+
+    assert!(super::similar::similar(&pool, "tt0000000", 10)
+        .await
+        .is_err());
+    Ok(())
+}
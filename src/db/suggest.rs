@@ -0,0 +1,234 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, Pool, Sqlite, SqliteConnection, SqlitePool};
+
+/// Upper bound of a prefix range: sorts after any string starting with the
+/// prefix.
+const PREFIX_END: char = '\u{10FFFF}';
+/// Shortest prefix anything is suggested for.
+const MIN_PREFIX: usize = 2;
+/// Prefixes up to this long match too many terms to rank on every
+/// keystroke, so their best suggestions are ranked ahead of time.
+const SHORT_PREFIX: usize = 3;
+/// Most suggestions returned at once, and kept per short prefix.
+pub const MAX_SUGGESTIONS: i64 = 50;
+
+/// A title or person to complete the search box with.
+#[derive(Debug, Serialize, FromRow)]
+pub struct Suggestion {
+    /// `title` or `name`
+    pub kind: String,
+    /// tconst or nconst
+    pub id: String,
+    pub label: String,
+    /// type and year of a title, main profession of a person
+    pub detail: Option<String>,
+    /// votes of the title, or summed over the titles a person is known for
+    pub popularity: i64,
+}
+
+/// `suggestions` holds one row per lowercased search term: the whole label
+/// and, for anything with votes, the label starting at each later word, so
+/// "godf" finds "The Godfather".
+pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS suggestions (
+                term TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                id TEXT NOT NULL,
+                label TEXT NOT NULL,
+                detail TEXT,
+                popularity INTEGER NOT NULL,
+                whole INTEGER NOT NULL
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS suggestions_term ON suggestions (term)")
+        .execute(pool)
+        .await?;
+
+    // the best suggestions for each short prefix, in order
+    let mut transaction = pool.begin().await?;
+    let exists: Option<i64> = sqlx::query_scalar(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'suggestion_prefixes'",
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS suggestion_prefixes (
+                prefix TEXT NOT NULL,
+                rank INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                id TEXT NOT NULL,
+                label TEXT NOT NULL,
+                detail TEXT,
+                popularity INTEGER NOT NULL,
+                PRIMARY KEY (prefix, rank)
+            )",
+    )
+    .execute(&mut *transaction)
+    .await?;
+    // a database from before the table has suggestions but no prefixes
+    if exists.is_none() {
+        build_prefixes(&mut transaction).await?;
+    }
+    transaction.commit().await
+}
+
+/// Ranks the suggestions for every short prefix the way [`suggest`] ranks
+/// a range of terms, keeping the best [`MAX_SUGGESTIONS`] of each.
+async fn build_prefixes(connection: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM suggestion_prefixes")
+        .execute(&mut *connection)
+        .await?;
+    for length in MIN_PREFIX..=SHORT_PREFIX {
+        sqlx::query(
+            r#"INSERT INTO suggestion_prefixes
+                    (prefix, rank, kind, id, label, detail, popularity)
+                SELECT prefix, rank, kind, id, label, detail, popularity FROM (
+                    SELECT prefix, kind, id, label, detail, popularity,
+                        row_number() OVER (
+                            PARTITION BY prefix
+                            ORDER BY exact DESC, whole DESC, popularity DESC, id
+                        ) AS rank
+                    FROM (
+                        SELECT substr(term, 1, ?1) AS prefix, kind, id, label, detail,
+                            popularity, max(length(term) = ?1) AS exact, max(whole) AS whole
+                        FROM suggestions
+                        WHERE length(term) >= ?1
+                        GROUP BY prefix, kind, id
+                    )
+                )
+                WHERE rank <= ?2"#,
+        )
+        .bind(length as i64)
+        .bind(MAX_SUGGESTIONS)
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Rebuilds `suggestions` from titles, names and ratings. Run after ingest.
+pub async fn build_index(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM suggestions")
+        .execute(&mut *transaction)
+        .await?;
+
+    // episodes and adult titles would crowd out everything else
+    sqlx::query(
+        r#"WITH RECURSIVE words (id, label, detail, popularity, rest, whole) AS (
+                SELECT t.tconst, t.primary_title,
+                    t.title_type || CASE WHEN t.start_year > 0 THEN ' ' || t.start_year ELSE '' END,
+                    COALESCE(r.num_votes, 0), lower(t.primary_title), 1
+                FROM titles AS t
+                LEFT JOIN ratings AS r ON r.tconst = t.tconst
+                WHERE t.title_type != 'tvEpisode' AND t.is_adult = 0
+                UNION ALL
+                SELECT id, label, detail, popularity, substr(rest, instr(rest, ' ') + 1), 0
+                FROM words
+                WHERE popularity > 0 AND instr(rest, ' ') > 0
+            )
+            INSERT INTO suggestions (term, kind, id, label, detail, popularity, whole)
+            SELECT rest, 'title', id, label, detail, popularity, whole
+            FROM words WHERE rest != ''"#,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query(
+        r#"WITH RECURSIVE words (id, label, detail, popularity, rest, whole) AS (
+                SELECT n.nconst, n.primary_name,
+                    NULLIF(substr(n.primary_profession, 1, instr(n.primary_profession || ',', ',') - 1), ''),
+                    COALESCE((
+                        SELECT sum(r.num_votes) FROM name_known_for AS k
+                        JOIN ratings AS r ON r.tconst = k.tconst
+                        WHERE k.nconst = n.nconst
+                    ), 0),
+                    lower(n.primary_name), 1
+                FROM names AS n
+                UNION ALL
+                SELECT id, label, detail, popularity, substr(rest, instr(rest, ' ') + 1), 0
+                FROM words
+                WHERE popularity > 0 AND instr(rest, ' ') > 0
+            )
+            INSERT INTO suggestions (term, kind, id, label, detail, popularity, whole)
+            SELECT rest, 'name', id, label, detail, popularity, whole
+            FROM words WHERE rest != ''"#,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    build_prefixes(&mut transaction).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Titles and people with a term starting with `text`: exact matches first,
+/// then matches on the start of the label, each by popularity. Nothing is
+/// suggested for fewer than [`MIN_PREFIX`] characters.
+pub async fn suggest(db: &SqlitePool, text: &str, limit: i64) -> Result<Vec<Suggestion>> {
+    // SQLite's lower() only folds ASCII
+    let prefix = text.trim().to_ascii_lowercase();
+    let length = prefix.chars().count();
+    if length < MIN_PREFIX {
+        return Ok(vec![]);
+    }
+    if length <= SHORT_PREFIX {
+        return Ok(sqlx::query_as::<_, Suggestion>(
+            r#"SELECT kind, id, label, detail, popularity FROM suggestion_prefixes
+                WHERE prefix = ? ORDER BY rank LIMIT ?"#,
+        )
+        .bind(&prefix)
+        .bind(limit)
+        .fetch_all(db)
+        .await?);
+    }
+    let end = format!("{prefix}{PREFIX_END}");
+
+    Ok(sqlx::query_as::<_, Suggestion>(
+        r#"SELECT kind, id, label, detail, popularity FROM suggestions
+            WHERE term >= ?1 AND term < ?2
+            GROUP BY kind, id
+            ORDER BY max(term = ?1) DESC, max(whole) DESC, popularity DESC, id
+            LIMIT ?3"#,
+    )
+    .bind(&prefix)
+    .bind(&end)
+    .bind(limit)
+    .fetch_all(db)
+    .await?)
+}
+
+#[tokio::test]
+async fn test_suggest() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let ids = |s: Vec<Suggestion>| s.into_iter().map(|s| s.id).collect::<Vec<_>>();
+
+    // more votes first
+    let godfather = suggest(&pool, "godf", 10).await?;
+    assert_eq!(ids(godfather), vec!["tt0068646", "tt0071562"]);
+
+    // a later word of a name, and the whole label beats a later word
+    let bird = suggest(&pool, "Bird", 10).await?;
+    assert_eq!(bird[0].id, "nm0083348");
+    assert_eq!(bird[0].kind, "name");
+
+    let the = suggest(&pool, "the incredibles", 10).await?;
+    assert_eq!(ids(the), vec!["tt0317705"]);
+
+    // episodes aren't suggested
+    assert!(suggest(&pool, "pilot", 10).await?.is_empty());
+
+    // short prefixes are ranked ahead of time, the same way
+    assert!(suggest(&pool, "g", 10).await?.is_empty());
+    let go = suggest(&pool, "go", 10).await?;
+    assert_eq!(ids(go), vec!["tt0068646", "tt0071562"]);
+    let bir = suggest(&pool, "bir", 10).await?;
+    assert_eq!(bir[0].id, "nm0083348");
+    Ok(())
+}
@@ -1,10 +1,14 @@
 use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use sqlx::{
-    query_builder::QueryBuilder, sqlite::SqliteRow, FromRow, Pool, Row, Sqlite, SqlitePool,
-    Transaction,
+    query_builder::QueryBuilder, sqlite::SqliteRow, Encode, FromRow, Pool, Row, Sqlite, SqlitePool,
+    Transaction, Type,
 };
 
+use super::{akas::Locale, ListTable};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Title {
     pub tconst: String,
@@ -16,8 +20,100 @@ pub struct Title {
     pub end_year: i64,
     pub runtime_minutes: i64,
     pub genres: String,
+    pub average_rating: Option<f64>,
+    pub num_votes: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_title: Option<String>,
+    /// set by [`super::akas::localize`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localized_title: Option<String>,
+}
+
+/// A page of titles from [`TitleQuery::fetch_page`].
+#[derive(Serialize, Debug)]
+pub struct Page {
+    pub results: Vec<Title>,
+    /// pass back as the cursor to get the next page
+    pub next_cursor: Option<String>,
+    /// number of titles matching the filters across all pages, counted on
+    /// the first page only
+    pub total: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    Relevance,
+    Year,
+    Title,
+    Runtime,
+    Rating,
+}
+
+impl Sort {
+    /// Column sorted on in the query built by [`TitleQuery`], bare so its
+    /// index can be used. Ingest stores 0 and '' for missing years, runtimes
+    /// and titles, leaving only the rating of unrated titles NULL.
+    fn expression(self) -> &'static str {
+        match self {
+            Sort::Relevance => "s.rank",
+            Sort::Year => "t.start_year",
+            Sort::Title => "t.primary_title",
+            Sort::Runtime => "t.runtime_minutes",
+            Sort::Rating => "r.average_rating",
+        }
+    }
+
+    /// The value of `expression` for `row`. Floats are kept as strings so
+    /// they parse back to exactly the same value, and a missing rating is
+    /// null.
+    fn value(self, row: &SqliteRow) -> Value {
+        match self {
+            Sort::Relevance => json!(row
+                .try_get::<Option<f64>, _>("rank")
+                .ok()
+                .flatten()
+                .unwrap_or(0.0)
+                .to_string()),
+            Sort::Year => json!(row.try_get::<i64, _>("start_year").unwrap_or(0)),
+            Sort::Title => json!(row
+                .try_get::<String, _>("primary_title")
+                .unwrap_or_default()),
+            Sort::Runtime => json!(row.try_get::<i64, _>("runtime_minutes").unwrap_or(0)),
+            Sort::Rating => match row.try_get::<Option<f64>, _>("average_rating") {
+                Ok(Some(rating)) => json!(rating.to_string()),
+                _ => Value::Null,
+            },
+        }
+    }
+}
+
+/// Position after the last row of a page: the sort it belongs to, that
+/// row's sort value and its tconst as a tie breaker.
+#[derive(Deserialize, Serialize)]
+struct Cursor(Option<Sort>, Value, String);
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| super::DBError::Invalid("cursor"))?;
+        Ok(serde_json::from_slice(&bytes).map_err(|_| super::DBError::Invalid("cursor"))?)
+    }
 }
 
+pub(super) const LISTS: &[ListTable] = &[ListTable {
+    table: "title_genres",
+    key: "tconst",
+    value: "genre",
+    column: 8,
+    parent: ("titles", "genres"),
+}];
+
 pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"CREATE TABLE IF NOT EXISTS titles (
@@ -52,10 +148,53 @@ pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query("CREATE INDEX IF NOT EXISTS title_akas_region ON title_akas (region, title_id)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"CREATE VIRTUAL TABLE IF NOT EXISTS titles_fts USING fts5 (
+                tconst UNINDEXED,
+                title,
+                tokenize = 'unicode61 remove_diacritics 2'
+            )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // the columns titles are sorted on, with tconst breaking ties
+    for column in ["start_year", "primary_title", "runtime_minutes"] {
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS titles_{column} ON titles ({column}, tconst)"
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    for list in LISTS {
+        list.init_table(pool).await?;
+    }
+
     Ok(())
 }
 
-pub struct TitleQuery<'a>(QueryBuilder<'a, Sqlite>);
+/// Builds `SELECT` over titles joined to their ratings with the filters,
+/// cursor, ordering and limit, alongside a count of every match for the
+/// first page. Sorting and paging compare the indexed columns themselves,
+/// so a page reads only its own rows.
+pub struct TitleQuery<'a> {
+    query: QueryBuilder<'a, Sqlite>,
+    /// the same filters, counting matches rather than selecting them
+    count: QueryBuilder<'a, Sqlite>,
+    filtered: bool,
+    /// built by [`Self::search`], so there is a relevance to sort on
+    search: bool,
+    sort: Option<Sort>,
+    descending: bool,
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
 
 impl<'r> FromRow<'r, SqliteRow> for Title {
     fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
@@ -69,35 +208,150 @@ impl<'r> FromRow<'r, SqliteRow> for Title {
             end_year: row.try_get("end_year").unwrap_or(0),
             runtime_minutes: row.try_get("runtime_minutes").unwrap_or(0),
             genres: row.try_get("genres").unwrap_or("".into()),
+            average_rating: row.try_get("average_rating").unwrap_or(None),
+            num_votes: row.try_get("num_votes").unwrap_or(None),
+            matched_title: row.try_get("matched_title").unwrap_or(None),
+            localized_title: None,
         })
     }
 }
 
 impl<'a> TitleQuery<'a> {
     pub fn new() -> Self {
-        TitleQuery(QueryBuilder::new("SELECT * FROM titles"))
+        TitleQuery {
+            query: QueryBuilder::new(
+                r#"SELECT t.*, r.average_rating, r.num_votes, NULL AS rank
+                    FROM titles AS t
+                    LEFT JOIN ratings AS r ON r.tconst = t.tconst"#,
+            ),
+            count: QueryBuilder::new("SELECT count(*) FROM titles AS t"),
+            filtered: false,
+            search: false,
+            sort: None,
+            descending: false,
+            cursor: None,
+            limit: None,
+        }
+    }
+
+    /// Full-text search over primary, original and alternate titles. Results
+    /// are ordered by relevance and carry the title that matched in
+    /// `matched_title`.
+    pub fn search(text: String) -> Self {
+        let Some(pattern) = fts_pattern(&text) else {
+            return Self::new();
+        };
+        let matches = r#"SELECT tconst, title AS matched_title, min(rank) AS rank
+            FROM titles_fts WHERE titles_fts MATCH "#;
+        let join = r#" GROUP BY tconst
+            ) AS s
+            JOIN titles AS t ON t.tconst = s.tconst"#;
+
+        let mut query = QueryBuilder::new(
+            "SELECT t.*, r.average_rating, r.num_votes, s.matched_title, s.rank FROM (",
+        );
+        query.push(matches);
+        query.push_bind(pattern.clone());
+        query.push(join);
+        query.push(" LEFT JOIN ratings AS r ON r.tconst = t.tconst");
+
+        let mut count = QueryBuilder::new("SELECT count(*) FROM (");
+        count.push(matches);
+        count.push_bind(pattern);
+        count.push(join);
+        TitleQuery {
+            query,
+            count,
+            filtered: false,
+            search: true,
+            sort: Some(Sort::Relevance),
+            descending: false,
+            cursor: None,
+            limit: None,
+        }
     }
 
     pub fn id(mut self, id: &'a String) -> Self {
         if !id.is_empty() {
             self.where_and();
-            self.0.push(" tconst = ");
-            self.0.push_bind(id);
+            self.push(" t.tconst = ");
+            self.push_bind(id);
+        }
+        self
+    }
+
+    pub fn ids(mut self, ids: &'a [String]) -> Self {
+        if !ids.is_empty() {
+            self.where_and();
+            self.push(" t.tconst IN (");
+            for (i, id) in ids.iter().enumerate() {
+                if i > 0 {
+                    self.push(", ");
+                }
+                self.push_bind(id);
+            }
+            self.push(")");
         }
         self
     }
 
     pub fn limit(mut self, number: i64) -> Self {
-        self.0.push(" LIMIT ");
-        self.0.push_bind(number);
+        self.limit = Some(number);
+        self
+    }
+
+    /// Sorts by `sort`, keeping the default order (relevance for a search)
+    /// when `None`. Relevance only applies to a search. Ties are broken by
+    /// tconst.
+    pub fn order_by(mut self, sort: Option<Sort>, descending: bool) -> Self {
+        if sort.is_some_and(|sort| sort != Sort::Relevance || self.search) {
+            self.sort = sort;
+        }
+        self.descending = descending;
         self
     }
+
+    /// Continue after the page `cursor` came from.
+    pub fn cursor(mut self, cursor: Option<String>) -> Self {
+        self.cursor = cursor.filter(|c| !c.is_empty());
+        self
+    }
+
     pub fn like(mut self, title: String) -> Self {
         if !title.is_empty() {
             self.where_and();
-            self.0.push(" original_title LIKE ");
-            self.0.push_bind(format!("{}%", title));
-            self.0.push(" COLLATE NOCASE ");
+            self.push(" original_title LIKE ");
+            self.push_bind(format!("{}%", title));
+            self.push(" COLLATE NOCASE ");
+        }
+        self
+    }
+
+    /// Like [`Self::like`], also matching the alternate titles used in
+    /// `locale`.
+    pub fn like_localized(mut self, title: String, locale: &Locale) -> Self {
+        if !title.is_empty() {
+            let pattern = format!("{}%", title);
+            self.where_and();
+            self.push(" (original_title LIKE ");
+            self.push_bind(pattern.clone());
+            self.push(
+                r#" COLLATE NOCASE OR EXISTS (SELECT 1 FROM title_akas AS a
+                    WHERE a.title_id = t.tconst AND a.title LIKE "#,
+            );
+            self.push_bind(pattern);
+            self.push(" COLLATE NOCASE");
+            if let Some(region) = locale.region.clone().filter(|r| !r.is_empty()) {
+                self.push(" AND a.region = ");
+                self.push_bind(region);
+                self.push(" COLLATE NOCASE");
+            }
+            if let Some(language) = locale.language.clone().filter(|l| !l.is_empty()) {
+                self.push(" AND a.language = ");
+                self.push_bind(language);
+                self.push(" COLLATE NOCASE");
+            }
+            self.push("))");
         }
         self
     }
@@ -105,86 +359,448 @@ impl<'a> TitleQuery<'a> {
     pub fn title_type(mut self, title_type: String) -> Self {
         if !title_type.is_empty() {
             self.where_and();
-            self.0.push(" title_type = ");
-            self.0.push_bind(title_type);
+            self.push(" title_type = ");
+            self.push_bind(title_type);
+        }
+        self
+    }
+
+    /// Titles tagged with every one of `genres`.
+    pub fn genres(mut self, genres: Vec<String>) -> Self {
+        for genre in genres.into_iter().filter(|g| !g.is_empty()) {
+            self.where_and();
+            self.push(
+                " EXISTS (SELECT 1 FROM title_genres AS g WHERE g.tconst = t.tconst AND g.genre = ",
+            );
+            self.push_bind(genre);
+            self.push(" COLLATE NOCASE)");
+        }
+        self
+    }
+
+    pub fn director(mut self, nconst: String) -> Self {
+        if !nconst.is_empty() {
+            self.where_and();
+            self.push(
+                " EXISTS (SELECT 1 FROM title_directors AS d WHERE d.tconst = t.tconst AND d.nconst = ",
+            );
+            self.push_bind(nconst);
+            self.push(")");
+        }
+        self
+    }
+
+    pub fn writer(mut self, nconst: String) -> Self {
+        if !nconst.is_empty() {
+            self.where_and();
+            self.push(
+                " EXISTS (SELECT 1 FROM title_writers AS w WHERE w.tconst = t.tconst AND w.nconst = ",
+            );
+            self.push_bind(nconst);
+            self.push(")");
         }
         self
     }
 
     pub fn start_year(mut self, year: Option<i64>) -> Self {
-        if year.is_some() {
+        if let Some(year) = year {
+            self.where_and();
+            self.push(" start_year = ");
+            self.push_bind(year);
+        }
+        self
+    }
+
+    /// Titles running in or after `year`. Series count until their
+    /// `end_year`, or indefinitely while still running.
+    pub fn year_from(mut self, year: Option<i64>) -> Self {
+        if let Some(year) = year {
+            self.where_and();
+            self.push(
+                r#" (CASE
+                    WHEN t.end_year > 0 THEN t.end_year
+                    WHEN t.title_type IN ('tvSeries', 'tvMiniSeries') THEN 9999
+                    ELSE t.start_year
+                END) >= "#,
+            );
+            self.push_bind(year);
+        }
+        self
+    }
+
+    /// Titles that started in or before `year`.
+    pub fn year_to(mut self, year: Option<i64>) -> Self {
+        if let Some(year) = year {
+            self.where_and();
+            self.push(" t.start_year > 0 AND t.start_year <= ");
+            self.push_bind(year);
+        }
+        self
+    }
+
+    pub fn runtime_min(mut self, minutes: Option<i64>) -> Self {
+        if let Some(minutes) = minutes {
+            self.where_and();
+            self.push(" t.runtime_minutes >= ");
+            self.push_bind(minutes);
+        }
+        self
+    }
+
+    pub fn runtime_max(mut self, minutes: Option<i64>) -> Self {
+        if let Some(minutes) = minutes {
             self.where_and();
-            self.0.push(" start_year = ");
-            self.0.push_bind(year.unwrap());
+            self.push(" t.runtime_minutes > 0 AND t.runtime_minutes <= ");
+            self.push_bind(minutes);
         }
         self
     }
 
+    pub fn exclude_adult(mut self, exclude: bool) -> Self {
+        if exclude {
+            self.where_and();
+            self.push(" t.is_adult = 0");
+        }
+        self
+    }
+
+    /// Adds `sql` to both the query and the count.
+    fn push(&mut self, sql: &str) {
+        self.query.push(sql);
+        self.count.push(sql);
+    }
+
+    /// Binds `value` in both the query and the count.
+    fn push_bind<T>(&mut self, value: T)
+    where
+        T: 'a + Clone + Encode<'a, Sqlite> + Type<Sqlite>,
+    {
+        self.query.push_bind(value.clone());
+        self.count.push_bind(value);
+    }
+
     fn where_and(&mut self) {
-        if !self.0.sql().contains("WHERE") {
-            self.0.push(" WHERE");
+        if !self.filtered {
+            self.push(" WHERE");
+            self.filtered = true;
         } else {
-            self.0.push(" AND");
+            self.push(" AND");
         }
     }
 
+    fn finish(&mut self) -> Result<()> {
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        let comparison = if self.descending { "<" } else { ">" };
+        if let Some(cursor) = self.cursor.take() {
+            let Cursor(sort, value, tconst) = Cursor::decode(&cursor)?;
+            if sort != self.sort {
+                return Err(super::DBError::Invalid("cursor for this sort").into());
+            }
+            self.query
+                .push(if self.filtered { " AND (" } else { " WHERE (" });
+            match (self.sort, value) {
+                (None, _) => {
+                    self.query.push(format!("t.tconst {comparison} "));
+                    self.query.push_bind(tconst);
+                }
+                // titles without a rating sort first, before any rated one
+                (Some(sort @ Sort::Rating), Value::Null) => {
+                    let expression = sort.expression();
+                    self.query
+                        .push(format!("{expression} IS NULL AND t.tconst {comparison} "));
+                    self.query.push_bind(tconst);
+                    if !self.descending {
+                        self.query.push(format!(" OR {expression} IS NOT NULL"));
+                    }
+                }
+                (Some(sort), value) => {
+                    self.query
+                        .push(format!("({}, t.tconst) {comparison} (", sort.expression()));
+                    match (sort, value) {
+                        (Sort::Title, Value::String(title)) => {
+                            self.query.push_bind(title);
+                        }
+                        (Sort::Year | Sort::Runtime, Value::Number(n)) if n.is_i64() => {
+                            self.query.push_bind(n.as_i64());
+                        }
+                        (Sort::Relevance | Sort::Rating, Value::String(n)) => {
+                            let n: f64 =
+                                n.parse().map_err(|_| super::DBError::Invalid("cursor"))?;
+                            self.query.push_bind(n);
+                        }
+                        _ => return Err(super::DBError::Invalid("cursor").into()),
+                    }
+                    self.query.push(", ");
+                    self.query.push_bind(tconst);
+                    self.query.push(")");
+                    if sort == Sort::Rating && self.descending {
+                        self.query
+                            .push(format!(" OR {} IS NULL", sort.expression()));
+                    }
+                }
+            }
+            self.query.push(")");
+        }
+
+        self.query.push(" ORDER BY ");
+        if let Some(sort) = self.sort {
+            self.query
+                .push(format!("{} {direction}, ", sort.expression()));
+        }
+        self.query.push(format!("t.tconst {direction}"));
+
+        if let Some(limit) = self.limit {
+            self.query.push(" LIMIT ");
+            self.query.push_bind(limit);
+        }
+        Ok(())
+    }
+
     pub async fn fetch_one(mut self, db: &SqlitePool) -> Result<Title> {
-        Ok(self.0.build_query_as::<Title>().fetch_one(db).await?)
+        self.finish()?;
+        self.query
+            .build_query_as::<Title>()
+            .fetch_optional(db)
+            .await?
+            .ok_or(super::DBError::NotFound("title").into())
+    }
+    pub async fn fetch_optional(mut self, db: &SqlitePool) -> Result<Option<Title>> {
+        self.finish()?;
+        Ok(self
+            .query
+            .build_query_as::<Title>()
+            .fetch_optional(db)
+            .await?)
     }
     pub async fn fetch(mut self, db: &SqlitePool) -> Result<Vec<Title>> {
-        Ok(self.0.build_query_as::<Title>().fetch_all(db).await?)
+        self.finish()?;
+        Ok(self.query.build_query_as::<Title>().fetch_all(db).await?)
+    }
+
+    /// Fetches one page of at most `limit` titles, 100 by default. Only the
+    /// first page counts the matches.
+    pub async fn fetch_page(mut self, db: &SqlitePool) -> Result<Page> {
+        let total = match self.cursor {
+            None => Some(self.count.build_query_scalar().fetch_one(db).await?),
+            Some(_) => None,
+        };
+        let limit = self.limit.unwrap_or(100);
+        // one extra row tells us whether there is a next page
+        self.limit = Some(limit + 1);
+        self.finish()?;
+        let mut rows = self.query.build().fetch_all(db).await?;
+
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(|row| {
+                Cursor(
+                    self.sort,
+                    self.sort.map(|s| s.value(row)).unwrap_or(Value::Null),
+                    row.try_get("tconst").unwrap_or_default(),
+                )
+                .encode()
+            })
+        } else {
+            None
+        };
+        let results = rows
+            .iter()
+            .map(Title::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Page {
+            results,
+            next_cursor,
+            total,
+        })
     }
 }
 
+/// Turns free text into an FTS5 query where every word is a quoted prefix
+/// term, so user input can't produce FTS syntax errors.
+fn fts_pattern(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Rebuilds `titles_fts` from the titles and akas tables. Run after ingest.
+pub async fn build_search_index(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM titles_fts")
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(
+        r#"INSERT INTO titles_fts (tconst, title)
+                SELECT tconst, primary_title FROM titles
+                UNION SELECT tconst, original_title FROM titles
+                UNION SELECT title_id, title FROM title_akas"#,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
 pub async fn ingest(
     transaction: &mut Transaction<'_, Sqlite>,
-    record: &Vec<String>,
+    batch: &[Vec<String>],
 ) -> Result<(), sqlx::Error> {
-    if record.len() >= 9 {
-        let is_adult = record[4].parse::<i32>().unwrap_or(0); // is_adult
-        let start_year = record[5].parse::<i32>().unwrap_or(0); // start_year
-        let end_year = record[6].parse::<i32>().unwrap_or(0); // end_year
-        let runtime = record[7].parse::<i32>().unwrap_or(0); // runtime_minutes
-        sqlx::query!(r#"
-            INSERT OR IGNORE INTO titles
-                (tconst, title_type, primary_title, original_title, is_adult, start_year, end_year, runtime_minutes, genres)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-        "#,
-        record[0], // tconst,
-        record[1], // title_type, 
-        record[2], // primary_title, 
-        record[3], // original_title, 
-        is_adult, // is_adult, 
-        start_year, // start_year, 
-        end_year, // end_year, 
-        runtime,// 
-        record[8], // genres
-        )
-        .execute(&mut **transaction)
-        .await?;
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 9).collect();
+    for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT OR REPLACE INTO titles
+                (tconst, title_type, primary_title, original_title, is_adult, start_year, end_year, runtime_minutes, genres) "#,
+        );
+        query.push_values(chunk, |mut row, record| {
+            row.push_bind(&record[0]) // tconst
+                .push_bind(&record[1]) // title_type
+                .push_bind(&record[2]) // primary_title
+                .push_bind(&record[3]) // original_title
+                .push_bind(record[4].parse::<i32>().unwrap_or(0)) // is_adult
+                .push_bind(record[5].parse::<i32>().unwrap_or(0)) // start_year
+                .push_bind(record[6].parse::<i32>().unwrap_or(0)) // end_year
+                .push_bind(record[7].parse::<i32>().unwrap_or(0)) // runtime_minutes
+                .push_bind(&record[8]); // genres
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    for list in LISTS {
+        list.ingest(transaction, &records).await?;
     }
     Ok(())
 }
 pub async fn ingest_aka(
     transaction: &mut Transaction<'_, Sqlite>,
-    record: &Vec<String>,
+    batch: &[Vec<String>],
 ) -> Result<(), sqlx::Error> {
-    if record.len() >= 8 {
-        sqlx::query(
-            r#"INSERT OR IGNORE INTO title_akas 
-                (title_id, ordering, title, region, language, types, attributes, is_original_title)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
-        )
-        .bind(&record[0])
-        .bind(record[1].parse::<i32>().unwrap_or(0))
-        .bind(&record[2])
-        .bind(&record[3])
-        .bind(&record[4])
-        .bind(&record[5])
-        .bind(&record[6])
-        .bind(record[7].parse::<i32>().unwrap_or(0))
-        .execute(&mut **transaction)
+    let records: Vec<&Vec<String>> = batch.iter().filter(|r| r.len() >= 8).collect();
+    for chunk in records.chunks(super::INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::new(
+            r#"INSERT OR REPLACE INTO title_akas
+                (title_id, ordering, title, region, language, types, attributes, is_original_title) "#,
+        );
+        query.push_values(chunk, |mut row, record| {
+            row.push_bind(&record[0])
+                .push_bind(record[1].parse::<i32>().unwrap_or(0))
+                .push_bind(&record[2])
+                .push_bind(&record[3])
+                .push_bind(&record[4])
+                .push_bind(&record[5])
+                .push_bind(&record[6])
+                .push_bind(record[7].parse::<i32>().unwrap_or(0));
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_title_filters() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let ids = |titles: Vec<Title>| titles.into_iter().map(|t| t.tconst).collect::<Vec<_>>();
+
+    // Breaking Bad ran 2008-2013, so it overlaps a range inside that span
+    let running = TitleQuery::new()
+        .year_from(Some(2010))
+        .year_to(Some(2012))
+        .fetch(&pool)
+        .await?;
+    assert_eq!(ids(running), vec!["tt0903747"]);
+
+    let long = TitleQuery::new()
+        .title_type("movie".into())
+        .runtime_min(Some(180))
+        .exclude_adult(true)
+        .fetch(&pool)
+        .await?;
+    assert_eq!(ids(long), vec!["tt0071562"]);
+
+    let crime = TitleQuery::new()
+        .genres(vec!["crime".into(), "thriller".into()])
+        .runtime_max(Some(48))
+        .fetch(&pool)
         .await?;
+    assert_eq!(ids(crime), vec!["tt1054724", "tt1232244"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_title_pages() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let movies = || {
+        TitleQuery::new()
+            .title_type("movie".into())
+            .order_by(Some(Sort::Year), true)
+            .limit(2)
+    };
+    let first = movies().fetch_page(&pool).await?;
+    assert_eq!(first.total, Some(4));
+    let years = first
+        .results
+        .iter()
+        .map(|t| t.start_year)
+        .collect::<Vec<_>>();
+    assert_eq!(years, vec![2018, 2004]);
+
+    let second = movies().cursor(first.next_cursor).fetch_page(&pool).await?;
+    let years = second
+        .results
+        .iter()
+        .map(|t| t.start_year)
+        .collect::<Vec<_>>();
+    assert_eq!(years, vec![1974, 1972]);
+    assert!(second.next_cursor.is_none());
+    // only the first page is counted
+    assert_eq!(second.total, None);
+
+    let bad = movies().cursor(Some("nope".into())).fetch_page(&pool).await;
+    assert!(bad.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_title_pages_match_one_query() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+    let unrated: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM titles WHERE tconst NOT IN (SELECT tconst FROM ratings)",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert!(unrated > 0, "fixtures should have unrated titles");
+
+    for sort in [Sort::Year, Sort::Title, Sort::Runtime, Sort::Rating] {
+        for descending in [false, true] {
+            let query = || TitleQuery::new().order_by(Some(sort), descending);
+            let all: Vec<_> = query()
+                .fetch(&pool)
+                .await?
+                .into_iter()
+                .map(|t| t.tconst)
+                .collect();
+
+            let mut paged = vec![];
+            let mut cursor = None;
+            loop {
+                let page = query().cursor(cursor).limit(3).fetch_page(&pool).await?;
+                paged.extend(page.results.into_iter().map(|t| t.tconst));
+                cursor = page.next_cursor;
+                if cursor.is_none() {
+                    break;
+                }
+            }
+            assert_eq!(paged, all, "{sort:?} descending: {descending}");
+        }
     }
     Ok(())
 }
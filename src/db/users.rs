@@ -0,0 +1,212 @@
+use anyhow::Result;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{prelude::FromRow, Pool, Sqlite, SqlitePool};
+
+use super::DBError;
+
+/// How long a session lasts after logging in, in seconds.
+pub const SESSION_TTL: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    /// unix seconds
+    pub created_at: i64,
+}
+
+/// `users` holds accounts with an Argon2 password hash, salt included.
+/// `sessions` holds a SHA-256 of each session token, so a leaked database
+/// can't be used to log in.
+pub async fn init_table(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS users (
+                id INTEGER PRIMARY KEY,
+                username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                password_hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS sessions (
+                token_hash TEXT PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL
+            )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS sessions_expires_at ON sessions (expires_at)")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+fn valid_username(username: &str) -> bool {
+    (3..=32).contains(&username.len())
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Argon2 is deliberately slow, so hashing runs off the async workers.
+async fn hash_password(password: String) -> Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("hashing password: {e}"))
+    })
+    .await?
+}
+
+async fn verify_password(password: String, hash: String) -> Result<bool> {
+    tokio::task::spawn_blocking(move || {
+        let hash =
+            PasswordHash::new(&hash).map_err(|e| anyhow::anyhow!("reading password hash: {e}"))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok())
+    })
+    .await?
+}
+
+/// Creates an account. Usernames are 3 to 32 letters, digits, `_`, `-` or
+/// `.`, unique regardless of case; passwords at least 8 characters.
+pub async fn register(db: &SqlitePool, username: &str, password: &str) -> Result<User> {
+    if !valid_username(username) {
+        return Err(DBError::Invalid("username").into());
+    }
+    if password.chars().count() < 8 {
+        return Err(DBError::Invalid("password, expected at least 8 characters").into());
+    }
+    let hash = hash_password(password.to_string()).await?;
+
+    let user = sqlx::query_as::<_, User>(
+        "INSERT INTO users (username, password_hash, created_at)
+            VALUES (?, ?, CAST(strftime('%s', 'now') AS INTEGER))
+            ON CONFLICT (username) DO NOTHING
+            RETURNING id, username, created_at",
+    )
+    .bind(username)
+    .bind(hash)
+    .fetch_optional(db)
+    .await?;
+    user.ok_or(DBError::Conflict("username").into())
+}
+
+/// Hash a password is checked against when the username doesn't exist, so
+/// that takes as long as a wrong password and doesn't tell which usernames
+/// are taken.
+static UNKNOWN_USER_HASH: tokio::sync::OnceCell<String> = tokio::sync::OnceCell::const_new();
+
+/// The user with this username and password, `None` when either is wrong.
+pub async fn authenticate(db: &SqlitePool, username: &str, password: &str) -> Result<Option<User>> {
+    let row = sqlx::query_as::<_, (i64, String, String, i64)>(
+        "SELECT id, username, password_hash, created_at FROM users WHERE username = ?",
+    )
+    .bind(username)
+    .fetch_optional(db)
+    .await?;
+    let (user, hash) = match row {
+        Some((id, username, hash, created_at)) => (
+            Some(User {
+                id,
+                username,
+                created_at,
+            }),
+            hash,
+        ),
+        None => {
+            let hash = UNKNOWN_USER_HASH
+                .get_or_try_init(|| hash_password("not anyone's password".into()))
+                .await?;
+            (None, hash.clone())
+        }
+    };
+    if !verify_password(password.to_string(), hash).await? {
+        return Ok(None);
+    }
+    Ok(user)
+}
+
+fn token_hash(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Starts a session for `user_id` and returns its token, for the session
+/// cookie. Expired sessions are cleared on the way.
+pub async fn create_session(db: &SqlitePool, user_id: i64) -> Result<String> {
+    let mut token = [0u8; 32];
+    argon2::password_hash::rand_core::RngCore::fill_bytes(&mut OsRng, &mut token);
+    let token = URL_SAFE_NO_PAD.encode(token);
+
+    sqlx::query("DELETE FROM sessions WHERE expires_at <= CAST(strftime('%s', 'now') AS INTEGER)")
+        .execute(db)
+        .await?;
+    sqlx::query(
+        "INSERT INTO sessions (token_hash, user_id, expires_at)
+            VALUES (?, ?, CAST(strftime('%s', 'now') AS INTEGER) + ?)",
+    )
+    .bind(token_hash(&token))
+    .bind(user_id)
+    .bind(SESSION_TTL)
+    .execute(db)
+    .await?;
+    Ok(token)
+}
+
+/// The user logged in with `token`, unless the session expired or ended.
+pub async fn session_user(db: &SqlitePool, token: &str) -> Result<Option<User>> {
+    Ok(sqlx::query_as::<_, User>(
+        "SELECT u.id, u.username, u.created_at FROM sessions AS s
+            JOIN users AS u ON u.id = s.user_id
+            WHERE s.token_hash = ? AND s.expires_at > CAST(strftime('%s', 'now') AS INTEGER)",
+    )
+    .bind(token_hash(token))
+    .fetch_optional(db)
+    .await?)
+}
+
+pub async fn end_session(db: &SqlitePool, token: &str) -> Result<()> {
+    sqlx::query("DELETE FROM sessions WHERE token_hash = ?")
+        .bind(token_hash(token))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_users() -> Result<()> {
+    let pool = super::ingest::fixture_pool().await?;
+
+    let user = register(&pool, "vito", "corleone1").await?;
+    assert!(register(&pool, "Vito", "another password").await.is_err());
+    assert!(register(&pool, "no spaces", "corleone1").await.is_err());
+    assert!(register(&pool, "michael", "short").await.is_err());
+
+    assert!(authenticate(&pool, "vito", "wrong password")
+        .await?
+        .is_none());
+    assert!(authenticate(&pool, "nobody", "corleone1").await?.is_none());
+    let found = authenticate(&pool, "VITO", "corleone1").await?.unwrap();
+    assert_eq!(found.id, user.id);
+
+    let token = create_session(&pool, user.id).await?;
+    assert_eq!(session_user(&pool, &token).await?.unwrap().id, user.id);
+    assert!(session_user(&pool, "forged").await?.is_none());
+    end_session(&pool, &token).await?;
+    assert!(session_user(&pool, &token).await?.is_none());
+    Ok(())
+}
@@ -0,0 +1,384 @@
+use anyhow::{bail, Result};
+use flate2::read::MultiGzDecoder;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{header, Client, StatusCode};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{error, info};
+
+use crate::db::ingest::IMDB_FILES;
+
+const IMDB_URL: &str = "https://datasets.imdbws.com";
+
+/// Total size of the resource, from `Content-Range` on a partial or
+/// unsatisfiable range response (`bytes 0-9/N` or `bytes */N`), or
+/// `Content-Length` on a full one.
+fn total_size(response: &reqwest::Response, offset: u64) -> Option<u64> {
+    if matches!(
+        response.status(),
+        StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE
+    ) {
+        response
+            .headers()
+            .get(header::CONTENT_RANGE)?
+            .to_str()
+            .ok()?
+            .rsplit('/')
+            .next()?
+            .parse()
+            .ok()
+    } else {
+        response.content_length().map(|len| len + offset)
+    }
+}
+
+/// `path` with `suffix` added to its file name, so `x.tsv.gz` becomes
+/// `x.tsv.gz.part`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// What identifies the version of a resource for `If-Range`: a strong
+/// `ETag`, else `Last-Modified`. Weak ETags can't be used for ranges.
+fn validator(response: &reqwest::Response) -> Option<&header::HeaderValue> {
+    let headers = response.headers();
+    headers
+        .get(header::ETAG)
+        .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+        .or(headers.get(header::LAST_MODIFIED))
+}
+
+/// Streams `url` to `output_path`, resuming from a `.part` file left by an
+/// earlier attempt. The validator of the response the `.part` file came
+/// from is kept beside it and sent as `If-Range`, so a file that changed
+/// upstream in between is downloaded again rather than spliced onto the
+/// old one. The file only gets its final name once its size checks out.
+async fn download_file(client: &Client, url: &str, output_path: &Path) -> Result<()> {
+    let part_path = with_suffix(output_path, ".part");
+    let validator_path = with_suffix(output_path, ".part.validator");
+    // without a validator there's no telling what the partial file holds
+    let saved = fs::read_to_string(&validator_path).ok();
+    let mut offset = match saved {
+        Some(_) => fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+
+    let mut request = client.get(url);
+    if let (true, Some(saved)) = (offset > 0, &saved) {
+        request = request
+            .header(header::RANGE, format!("bytes={offset}-"))
+            .header(header::IF_RANGE, saved.as_str());
+    }
+    let mut response = request.send().await?;
+
+    let mut file = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            info!("Resuming {} at {} bytes", url, offset);
+            OpenOptions::new().append(true).open(&part_path)?
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+            if total_size(&response, offset) == Some(offset) {
+                // the partial file is already complete
+                fs::rename(&part_path, output_path)?;
+                fs::remove_file(&validator_path)?;
+                return Ok(());
+            }
+            info!("Discarding partial download of {}", url);
+            fs::remove_file(&part_path)?;
+            fs::remove_file(&validator_path)?;
+            return Box::pin(download_file(client, url, output_path)).await;
+        }
+        _ => {
+            response = response.error_for_status()?;
+            offset = 0;
+            match validator(&response) {
+                Some(validator) => fs::write(&validator_path, validator.as_bytes())?,
+                None if saved.is_some() => fs::remove_file(&validator_path)?,
+                None => {}
+            }
+            File::create(&part_path)?
+        }
+    };
+    let total = total_size(&response, offset);
+
+    let pb = ProgressBar::new(total.unwrap_or(0));
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb.set_position(offset);
+
+    let mut written = offset;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        written += chunk.len() as u64;
+        pb.set_position(written);
+    }
+    file.flush()?;
+    pb.finish_with_message("Download completed");
+
+    if let Some(total) = total {
+        if written != total {
+            bail!("{url}: downloaded {written} bytes, expected {total}");
+        }
+    }
+    fs::rename(&part_path, output_path)?;
+    if validator_path.exists() {
+        fs::remove_file(&validator_path)?;
+    }
+    Ok(())
+}
+
+/// Decompresses `gz_path` to `output_path` by way of a `.part` file, so an
+/// interrupted run never leaves a truncated file that looks complete.
+fn decompress(gz_path: &Path, output_path: &Path) -> Result<()> {
+    info!("Decompressing {}...", gz_path.display());
+    let part_path = with_suffix(output_path, ".part");
+    // like ingest, read every member of a gzip made of several
+    let mut decoder = MultiGzDecoder::new(BufReader::new(File::open(gz_path)?));
+    let mut output = BufWriter::new(File::create(&part_path)?);
+    io::copy(&mut decoder, &mut output)?;
+    output.flush()?;
+    drop(output);
+    fs::rename(&part_path, output_path)?;
+    fs::remove_file(gz_path)?;
+    Ok(())
+}
+
+async fn fetch_file(
+    client: &Client,
+    base_url: &str,
+    data_dir: &Path,
+    name: &str,
+    keep_gz: bool,
+) -> Result<()> {
+    let gz_path = data_dir.join(format!("{name}.tsv.gz"));
+    let tsv_path = data_dir.join(format!("{name}.tsv"));
+
+    if !gz_path.exists() {
+        download_file(client, &format!("{base_url}/{name}.tsv.gz"), &gz_path).await?;
+    }
+    if !keep_gz {
+        decompress(&gz_path, &tsv_path)?;
+    }
+    Ok(())
+}
+
+/// Downloads any IMDb dataset files missing from `data_dir`. With `keep_gz`
+/// the files are left compressed, which ingest reads directly.
+pub async fn fetch(data_dir: &Path, keep_gz: bool) -> Result<()> {
+    fetch_from(IMDB_URL, data_dir, keep_gz).await
+}
+
+pub async fn fetch_from(base_url: &str, data_dir: &Path, keep_gz: bool) -> Result<()> {
+    fs::create_dir_all(data_dir)?;
+
+    let client = Client::new();
+    let mut failed = vec![];
+    for (name, _) in IMDB_FILES {
+        let have_tsv = data_dir.join(format!("{name}.tsv")).exists();
+        let have_gz = data_dir.join(format!("{name}.tsv.gz")).exists();
+        if have_tsv || (keep_gz && have_gz) {
+            continue;
+        }
+        info!("Processing {}...", name);
+
+        match fetch_file(&client, base_url, data_dir, name, keep_gz).await {
+            Ok(_) => info!("Successfully processed {}", name),
+            Err(e) => {
+                error!("Error processing {}: {}", name, e);
+                failed.push(*name);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("could not fetch {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+/// Serves `dir` over HTTP on a random local port, standing in for IMDb.
+#[cfg(test)]
+async fn serve_dir(dir: &Path) -> Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let app = axum::Router::new().fallback_service(tower_http::services::ServeDir::new(dir));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok(format!("http://{addr}"))
+}
+
+#[cfg(test)]
+fn gzip_fixture(dir: &Path, name: &str) -> Result<Vec<u8>> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let fixture =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("fixtures/{name}.tsv")))?;
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(&fixture)?;
+    let gz = encoder.finish()?;
+    fs::write(dir.join(format!("{name}.tsv.gz")), &gz)?;
+    Ok(gz)
+}
+
+/// Serves `body` at every path with a strong `etag`, honouring `Range` and
+/// `If-Range` the way IMDb's CDN does.
+#[cfg(test)]
+async fn serve_bytes(body: Vec<u8>, etag: &'static str) -> Result<String> {
+    use axum::{
+        body::Bytes,
+        http::{header as h, HeaderMap, StatusCode as Status},
+        response::IntoResponse,
+    };
+
+    let body = Bytes::from(body);
+    let handler = move |headers: HeaderMap| async move {
+        let len = body.len();
+        let fresh = headers.get(h::IF_RANGE).is_none_or(|v| v == etag);
+        let start: Option<usize> = headers
+            .get(h::RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(|range| {
+                range
+                    .strip_prefix("bytes=")?
+                    .strip_suffix('-')?
+                    .parse()
+                    .ok()
+            })
+            .filter(|_| fresh);
+        match start {
+            Some(start) if start >= len => (
+                Status::RANGE_NOT_SATISFIABLE,
+                [(h::CONTENT_RANGE, format!("bytes */{len}"))],
+            )
+                .into_response(),
+            Some(start) => (
+                Status::PARTIAL_CONTENT,
+                [
+                    (h::CONTENT_RANGE, format!("bytes {start}-{}/{len}", len - 1)),
+                    (h::ETAG, etag.into()),
+                ],
+                body.slice(start..),
+            )
+                .into_response(),
+            None => ([(h::ETAG, etag)], body).into_response(),
+        }
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let app = axum::Router::new().fallback(handler);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok(format!("http://{addr}"))
+}
+
+#[tokio::test]
+async fn test_download_resume() -> Result<()> {
+    let remote = tempfile::tempdir()?;
+    let gz = gzip_fixture(remote.path(), "title.principals")?;
+    let url = serve_bytes(gz.clone(), "\"v2\"").await?;
+
+    let local = tempfile::tempdir()?;
+    let output = local.path().join("title.principals.tsv.gz");
+    let part = with_suffix(&output, ".part");
+    let validator = with_suffix(&output, ".part.validator");
+    let client = Client::new();
+    let download = || download_file(&client, &url, &output);
+
+    // resumes a partial file of the same version
+    fs::write(&part, &gz[..gz.len() / 2])?;
+    fs::write(&validator, "\"v2\"")?;
+    download().await?;
+    assert_eq!(fs::read(&output)?, gz);
+    assert!(!part.exists() && !validator.exists());
+
+    // starts over when the file changed upstream
+    fs::write(&part, b"bytes of an older version")?;
+    fs::write(&validator, "\"v1\"")?;
+    download().await?;
+    assert_eq!(fs::read(&output)?, gz);
+
+    // or when there's nothing to tell what the partial file holds
+    fs::write(&part, b"bytes of some version")?;
+    download().await?;
+    assert_eq!(fs::read(&output)?, gz);
+    assert!(!part.exists() && !validator.exists());
+
+    // a partial file that is already whole is kept
+    fs::write(&part, &gz)?;
+    fs::write(&validator, "\"v2\"")?;
+    download().await?;
+    assert_eq!(fs::read(&output)?, gz);
+
+    // but one longer than the file is not
+    fs::write(&part, [gz.as_slice(), b"trailing"].concat())?;
+    fs::write(&validator, "\"v2\"")?;
+    download().await?;
+    assert_eq!(fs::read(&output)?, gz);
+    assert!(!part.exists() && !validator.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fetch() -> Result<()> {
+    let remote = tempfile::tempdir()?;
+    for (name, _) in IMDB_FILES {
+        gzip_fixture(remote.path(), name)?;
+    }
+    let base_url = serve_dir(remote.path()).await?;
+
+    let local = tempfile::tempdir()?;
+    fetch_from(&base_url, local.path(), false).await?;
+    assert_eq!(
+        fs::read(local.path().join("title.ratings.tsv"))?,
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/title.ratings.tsv"))?
+    );
+    assert!(!local.path().join("title.ratings.tsv.gz").exists());
+    assert!(!local.path().join("title.ratings.tsv.part").exists());
+
+    let local = tempfile::tempdir()?;
+    fetch_from(&base_url, local.path(), true).await?;
+    assert!(local.path().join("title.crew.tsv.gz").exists());
+    assert!(!local.path().join("title.crew.tsv").exists());
+
+    // a missing file fails the fetch, after the others are fetched
+    fs::remove_file(remote.path().join("title.akas.tsv.gz"))?;
+    let local = tempfile::tempdir()?;
+    let error = fetch_from(&base_url, local.path(), false)
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), "could not fetch title.akas");
+    assert!(local.path().join("title.crew.tsv").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fetch_multi_member() -> Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let remote = tempfile::tempdir()?;
+    for (name, _) in IMDB_FILES {
+        gzip_fixture(remote.path(), name)?;
+    }
+    // title.ratings as two gzip members one after the other
+    let fixture =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/title.ratings.tsv"))?;
+    let (first, second) = fixture.split_at(fixture.len() / 2);
+    let mut gz = vec![];
+    for part in [first, second] {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(part)?;
+        gz.extend(encoder.finish()?);
+    }
+    fs::write(remote.path().join("title.ratings.tsv.gz"), gz)?;
+    let base_url = serve_dir(remote.path()).await?;
+
+    let local = tempfile::tempdir()?;
+    fetch_from(&base_url, local.path(), false).await?;
+    assert_eq!(fs::read(local.path().join("title.ratings.tsv"))?, fixture);
+    Ok(())
+}
@@ -1,6 +1,4 @@
 mod page;
 pub(crate) use page::page;
-mod res;
-pub(crate) use res::res;
 mod router;
 pub(crate) use router::router;
@@ -1,51 +1,19 @@
+/// Renders `$page_path` with `$struct` as its context, evaluating to
+/// `Result<Html<String>, AppError>`.
 macro_rules! page {
     ($state:expr, $page_path:expr) => {{
-        let page = match $state
+        $state
             .tera
             .render($page_path, &tera::Context::new())
             .map(Html)
-        {
-            Ok(p) => p,
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrResponse {
-                        error: e.to_string(),
-                    })
-                    .into_response(),
-                );
-            }
-        };
-        (StatusCode::OK, page.into_response())
+            .map_err(AppError::from)
     }};
 
     ($state:expr, $page_path:expr,$struct:expr) => {{
-        let context = match tera::Context::from_serialize($struct) {
-            Ok(c) => c,
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrResponse {
-                        error: e.to_string(),
-                    })
-                    .into_response(),
-                );
-            }
-        };
-
-        let page = match $state.tera.render($page_path, &context).map(Html) {
-            Ok(p) => p,
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrResponse {
-                        error: e.to_string(),
-                    })
-                    .into_response(),
-                );
-            }
-        };
-        (StatusCode::OK, page.into_response())
+        tera::Context::from_serialize($struct)
+            .and_then(|context| $state.tera.render($page_path, &context))
+            .map(Html)
+            .map_err(AppError::from)
     }};
 }
 
@@ -1,22 +1,37 @@
+mod config;
 mod db;
+mod fetch;
 mod macros;
 mod routes;
 
 use anyhow::Result;
 use axum::http::Method;
-use sqlx::sqlite::SqlitePoolOptions;
-use std::{env, net::SocketAddr, sync::Arc};
+use clap::Parser;
+use std::{net::SocketAddr, sync::Arc};
 use tera::Tera;
-use tokio;
 use tower_http::{
     cors::CorsLayer,
     trace::{DefaultMakeSpan, TraceLayer},
 };
 use tracing::{error, info};
 
+use config::{Cli, Command, Config};
+
 pub struct AppState {
     db: Arc<sqlx::SqlitePool>,
     tera: Arc<Tera>,
+    /// collaboration graph, loaded on the first path search
+    graph: tokio::sync::OnceCell<db::graph::Graph>,
+    /// see [`Config::secure_cookies`]
+    secure_cookies: bool,
+}
+
+impl AppState {
+    async fn graph(&self) -> anyhow::Result<&db::graph::Graph> {
+        self.graph
+            .get_or_try_init(|| db::graph::Graph::load(&self.db))
+            .await
+    }
 }
 
 #[tokio::main]
@@ -25,14 +40,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // .with_max_level(tracing::Level::DEBUG)
         .init();
 
-    if env::var("INGEST_MOVIES").is_ok() {
-        let ingest_client = db::ingest::IngestClient::new("sqlite:movies.db").await?;
-        ingest_client.start().await?;
-        info!("All data imports completed");
-        return Ok(());
+    let cli = Cli::parse();
+    let config = Config::load(&cli)?;
+
+    match cli.command {
+        Command::Serve { .. } => serve(&config).await?,
+        Command::Ingest { incremental, .. } => {
+            let ingest_client = db::ingest::IngestClient::new(&config.database)
+                .await?
+                .data_dir(&config.data_dir)
+                .incremental(incremental);
+            ingest_client.start().await?;
+            info!("All data imports completed");
+        }
+        Command::Fetch { keep_gz, .. } => {
+            fetch::fetch(&config.data_dir, keep_gz).await?;
+            info!("All downloads completed");
+        }
+        Command::Search { text, limit } => {
+            let pool = db::connect(&config.database).await?;
+            let titles = db::titles::TitleQuery::search(text.join(" "))
+                .limit(limit)
+                .fetch(&pool)
+                .await?;
+            for title in titles {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    title.tconst, title.title_type, title.start_year, title.primary_title
+                );
+            }
+        }
+        Command::Stats => {
+            let pool = db::connect(&config.database).await?;
+            for (table, count) in db::stats(&pool).await? {
+                println!("{table}\t{count}");
+            }
+        }
     }
 
-    let tera = match Tera::new("templates/**/*.html") {
+    Ok(())
+}
+
+async fn serve(config: &Config) -> Result<()> {
+    let tera = match Tera::new(&config.templates) {
         Ok(t) => t,
         Err(e) => {
             error!("Parsing error(s): {}", e);
@@ -41,14 +91,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     let tera = Arc::new(tera);
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect("sqlite:movies.db")
-        .await?;
+    let pool = db::connect(&config.database).await?;
     db::init_tables(&pool).await?;
     let db = Arc::new(pool);
 
-    let cors = CorsLayer::new().allow_methods([Method::GET, Method::POST]);
+    let cors =
+        CorsLayer::new().allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE]);
     // .allow_headers([header::CONTENT_TYPE, header::ACCEPT, header::AUTHORIZATION]);
 
     // build our application with a route
@@ -58,18 +106,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
         )
         .layer(cors)
-        .with_state(Arc::new(AppState { db, tera }));
+        .with_state(Arc::new(AppState {
+            db,
+            tera,
+            graph: tokio::sync::OnceCell::new(),
+            secure_cookies: config.secure_cookies,
+        }));
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
-        .await
-        .unwrap();
+    info!("listening on {}", config.bind);
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
     axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
-    .await
-    .unwrap();
+    .await?;
 
     Ok(())
 }
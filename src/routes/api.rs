@@ -1,65 +1,445 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::State,
+    http::{HeaderMap, StatusCode},
     Json,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tracing::{error, info};
+use tracing::info;
 
 use crate::{
-    db::{movie, titles},
-    macros::res,
-    routes::ErrResponse,
+    db::{
+        akas::{self, Aka, Locale},
+        collaborators::{self, Collaborator, SimilarCast},
+        graph::{self, Connection},
+        lists::{self, List, ListItem, ListItems, ListKind},
+        movie::{self, Movie},
+        names::{self, Name},
+        person::{self, Person},
+        series::{self, Series},
+        similar::{self, Recommendation},
+        suggest::{self, Suggestion},
+        titles::{self, Page},
+        users::{self, User},
+    },
+    routes::{
+        auth::{self, CurrentUser, SessionCookie},
+        cache::Cache,
+        error::{AppError, AppJson, AppPath, AppQuery},
+    },
 };
 
+/// A JSON array, or a comma-separated string in a query string.
+fn comma_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Joined(String),
+        Items(Vec<String>),
+    }
+    Ok(match List::deserialize(deserializer)? {
+        List::Joined(joined) => joined
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        List::Items(items) => items,
+    })
+}
+
+/// Title search parameters, as a JSON body for `POST /api` or a query string
+/// for `GET /api/titles`.
 #[derive(Debug, Deserialize)]
 pub struct Request {
+    #[serde(default)]
     title: String,
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default)]
     title_type: String,
     year: Option<i64>, // TODO: check js
+    /// use the full-text index instead of a title prefix match
+    #[serde(default)]
+    search: bool,
+    #[serde(default, deserialize_with = "comma_list")]
+    genres: Vec<String>,
+    /// first year of a range, series count while running
+    from: Option<i64>,
+    /// last year of a range
+    to: Option<i64>,
+    runtime_min: Option<i64>,
+    runtime_max: Option<i64>,
+    #[serde(default)]
+    exclude_adult: bool,
+    /// nconst of a director
+    #[serde(default)]
+    director: String,
+    /// nconst of a writer
+    #[serde(default)]
+    writer: String,
+    /// defaults to relevance for a search
+    order_by: Option<titles::Sort>,
+    #[serde(default)]
+    desc: bool,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+    /// page size, at most 100
+    limit: Option<i64>,
+    /// also match and show the titles used in this region
+    region: Option<String>,
+    /// and language
+    lang: Option<String>,
+}
+
+async fn find_titles(state: &crate::AppState, req: Request) -> Result<Page, AppError> {
+    let locale = Locale {
+        region: req.region,
+        language: req.lang,
+    };
+    let query = if req.search {
+        titles::TitleQuery::search(req.title)
+    } else if locale.is_empty() {
+        titles::TitleQuery::new().like(req.title)
+    } else {
+        titles::TitleQuery::new().like_localized(req.title, &locale)
+    };
+    let mut titles = query
+        .title_type(req.title_type)
+        .genres(req.genres)
+        .director(req.director)
+        .writer(req.writer)
+        .start_year(req.year)
+        .year_from(req.from)
+        .year_to(req.to)
+        .runtime_min(req.runtime_min)
+        .runtime_max(req.runtime_max)
+        .exclude_adult(req.exclude_adult)
+        .order_by(req.order_by, req.desc)
+        .cursor(req.cursor)
+        .limit(req.limit.unwrap_or(100).clamp(1, 100))
+        .fetch_page(&state.db)
+        .await?;
+    akas::localize(&state.db, &mut titles.results, &locale).await?;
+    Ok(titles)
 }
+
 pub async fn root(
     State(state): State<Arc<crate::AppState>>,
-    Json(req): Json<Request>,
-) -> impl IntoResponse {
+    AppJson(req): AppJson<Request>,
+) -> Result<Json<Page>, AppError> {
+    info!("request {req:?}");
+    Ok(Json(find_titles(&state, req).await?))
+}
+
+pub async fn titles(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppQuery(req): AppQuery<Request>,
+) -> Result<(Cache, Json<Page>), AppError> {
     info!("request {req:?}");
-    let titles = res!(
-        titles::TitleQuery::new()
-            .like(req.title)
-            .title_type(req.title_type)
-            .start_year(req.year)
-            .limit(100)
-            .fetch(&state.db)
-            .await,
-        (
-            StatusCode::NOT_FOUND,
-            Json(ErrResponse {
-                error: "not found".into(),
-            })
-            .into_response(),
-        )
-    );
-    (StatusCode::OK, Json(titles).into_response())
+    Ok((cache, Json(find_titles(&state, req).await?)))
+}
+
+pub async fn title(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppPath(id): AppPath<String>,
+    AppQuery(locale): AppQuery<Locale>,
+) -> Result<(Cache, Json<Movie>), AppError> {
+    info!("request {id:?} {locale:?}");
+    let movie = movie::get(&state.db, id, &locale).await?;
+
+    Ok((cache, Json(movie)))
 }
 
 pub async fn item(
     State(state): State<Arc<crate::AppState>>,
-    Path(id): Path<String>,
-) -> impl IntoResponse {
+    AppPath(id): AppPath<String>,
+    AppQuery(locale): AppQuery<Locale>,
+) -> Result<Json<Movie>, AppError> {
+    info!("request {id:?} {locale:?}");
+    let movie = movie::get(&state.db, id, &locale).await?;
+
+    Ok(Json(movie))
+}
+
+pub async fn name(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppPath(id): AppPath<String>,
+) -> Result<(Cache, Json<Person>), AppError> {
+    info!("request {id:?}");
+    let person = person::get(&state.db, id).await?;
+
+    Ok((cache, Json(person)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NamesRequest {
+    /// start of the name, any case
+    #[serde(default)]
+    name: String,
+    /// e.g. `director` or `composer`
+    #[serde(default)]
+    profession: String,
+    /// at most 100, 20 by default
+    limit: Option<i64>,
+}
+pub async fn names(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppQuery(req): AppQuery<NamesRequest>,
+) -> Result<(Cache, Json<Vec<Name>>), AppError> {
+    info!("request {req:?}");
+    let names = names::NameQuery::new()
+        .like(req.name)
+        .profession(req.profession)
+        .limit(req.limit.unwrap_or(20).clamp(1, 100))
+        .fetch(&state.db)
+        .await?;
+
+    Ok((cache, Json(names)))
+}
+
+pub async fn series(
+    State(state): State<Arc<crate::AppState>>,
+    AppPath(id): AppPath<String>,
+) -> Result<Json<Series>, AppError> {
+    info!("request {id:?}");
+    let series = series::get(&state.db, id).await?;
+
+    Ok(Json(series))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AkasRequest {
+    #[serde(flatten)]
+    locale: Locale,
+    /// e.g. `imdbDisplay` or `working`
+    #[serde(default, rename = "type")]
+    aka_type: String,
+}
+pub async fn akas(
+    State(state): State<Arc<crate::AppState>>,
+    AppPath(id): AppPath<String>,
+    AppQuery(req): AppQuery<AkasRequest>,
+) -> Result<Json<Vec<Aka>>, AppError> {
+    info!("request {id:?} {req:?}");
+    let akas = akas::AkaQuery::new()
+        .title(&id)
+        .locale(&req.locale)
+        .aka_type(req.aka_type)
+        .fetch(&state.db)
+        .await?;
+
+    Ok(Json(akas))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestRequest {
+    /// at least 2 characters
+    #[serde(default)]
+    q: String,
+    /// at most 50, 10 by default
+    limit: Option<i64>,
+}
+pub async fn suggest(
+    State(state): State<Arc<crate::AppState>>,
+    AppQuery(req): AppQuery<SuggestRequest>,
+) -> Result<Json<Vec<Suggestion>>, AppError> {
+    let limit = req.limit.unwrap_or(10).clamp(1, suggest::MAX_SUGGESTIONS);
+    let suggestions = suggest::suggest(&state.db, &req.q, limit).await?;
+
+    Ok(Json(suggestions))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PathRequest {
+    /// nconst to start from
+    pub from: String,
+    /// nconst to reach
+    pub to: String,
+}
+pub async fn path(
+    State(state): State<Arc<crate::AppState>>,
+    AppQuery(req): AppQuery<PathRequest>,
+) -> Result<Json<Connection>, AppError> {
+    info!("request {req:?}");
+    let graph = state.graph().await?;
+    let connection = graph::connect(&state.db, graph, &req.from, &req.to)
+        .await?
+        .ok_or(AppError::NotFound("no connection".into()))?;
+
+    Ok(Json(connection))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LimitRequest {
+    /// at most 100, 20 by default
+    limit: Option<i64>,
+}
+impl LimitRequest {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(20).clamp(1, 100)
+    }
+}
+
+pub async fn collaborators(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppPath(id): AppPath<String>,
+    AppQuery(req): AppQuery<LimitRequest>,
+) -> Result<(Cache, Json<BTreeMap<String, Vec<Collaborator>>>), AppError> {
+    info!("request {id:?} {req:?}");
+    let collaborators = collaborators::collaborators(&state.db, &id, req.limit()).await?;
+
+    Ok((cache, Json(collaborators)))
+}
+
+pub async fn similar_cast(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppPath(id): AppPath<String>,
+    AppQuery(req): AppQuery<LimitRequest>,
+) -> Result<(Cache, Json<Vec<SimilarCast>>), AppError> {
+    info!("request {id:?} {req:?}");
+    let similar = collaborators::similar_cast(&state.db, &id, req.limit()).await?;
+
+    Ok((cache, Json(similar)))
+}
+
+pub async fn similar(
+    State(state): State<Arc<crate::AppState>>,
+    cache: Cache,
+    AppPath(id): AppPath<String>,
+    AppQuery(req): AppQuery<LimitRequest>,
+) -> Result<(Cache, Json<Vec<Recommendation>>), AppError> {
+    info!("request {id:?} {req:?}");
+    let similar = similar::similar(&state.db, &id, req.limit()).await?;
+
+    Ok((cache, Json(similar)))
+}
+
+pub async fn lists(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<Vec<List>>, AppError> {
+    Ok(Json(lists::all(&state.db, user.id).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewListRequest {
+    kind: ListKind,
+    /// named after the kind by default
+    name: Option<String>,
+}
+pub async fn create_list(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+    AppJson(req): AppJson<NewListRequest>,
+) -> Result<(StatusCode, Json<List>), AppError> {
+    info!("request {req:?}");
+    let list = lists::create(&state.db, user.id, req.kind, req.name).await?;
+
+    Ok((StatusCode::CREATED, Json(list)))
+}
+
+pub async fn list(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+    AppPath(id): AppPath<i64>,
+) -> Result<Json<ListItems>, AppError> {
+    info!("request {id:?}");
+    Ok(Json(lists::items(&state.db, id, user.id).await?))
+}
+
+pub async fn delete_list(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+    AppPath(id): AppPath<i64>,
+) -> Result<StatusCode, AppError> {
     info!("request {id:?}");
-    let movie = res!(
-        movie::get(&state.db, id).await,
-        (
-            StatusCode::NOT_FOUND,
-            Json(ErrResponse {
-                error: "not found".into(),
-            })
-            .into_response(),
-        )
-    );
-
-    (StatusCode::OK, Json(movie).into_response())
+    lists::delete(&state.db, id, user.id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListItemRequest {
+    note: Option<String>,
+    /// 1 to 10
+    rating: Option<i64>,
+}
+pub async fn put_list_item(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+    AppPath((id, tconst)): AppPath<(i64, String)>,
+    AppJson(req): AppJson<ListItemRequest>,
+) -> Result<Json<ListItem>, AppError> {
+    info!("request {id:?} {tconst:?} {req:?}");
+    let item = lists::put_item(&state.db, id, user.id, &tconst, req.note, req.rating).await?;
+
+    Ok(Json(item))
+}
+
+pub async fn delete_list_item(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+    AppPath((id, tconst)): AppPath<(i64, String)>,
+) -> Result<StatusCode, AppError> {
+    info!("request {id:?} {tconst:?}");
+    lists::remove_item(&state.db, id, user.id, &tconst).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Not `Debug`, so the password stays out of the logs.
+#[derive(Deserialize)]
+pub struct CredentialsRequest {
+    username: String,
+    password: String,
+}
+
+/// Creates an account and logs it in.
+pub async fn register(
+    State(state): State<Arc<crate::AppState>>,
+    AppJson(req): AppJson<CredentialsRequest>,
+) -> Result<(StatusCode, SessionCookie, Json<User>), AppError> {
+    info!("request {:?}", req.username);
+    let user = users::register(&state.db, &req.username, &req.password).await?;
+    let token = users::create_session(&state.db, user.id).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        SessionCookie::new(&state, Some(token)),
+        Json(user),
+    ))
+}
+
+pub async fn login(
+    State(state): State<Arc<crate::AppState>>,
+    AppJson(req): AppJson<CredentialsRequest>,
+) -> Result<(SessionCookie, Json<User>), AppError> {
+    info!("request {:?}", req.username);
+    let user = users::authenticate(&state.db, &req.username, &req.password)
+        .await?
+        .ok_or(AppError::Unauthorized("wrong username or password".into()))?;
+    let token = users::create_session(&state.db, user.id).await?;
+
+    Ok((SessionCookie::new(&state, Some(token)), Json(user)))
+}
+
+/// Ends the session, if any, and clears the cookie either way.
+pub async fn logout(
+    State(state): State<Arc<crate::AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, SessionCookie, ()), AppError> {
+    if let Some(token) = auth::session_token(&headers) {
+        users::end_session(&state.db, &token).await?;
+    }
+
+    Ok((StatusCode::NO_CONTENT, SessionCookie::new(&state, None), ()))
+}
+
+pub async fn me(CurrentUser(user): CurrentUser) -> Json<User> {
+    Json(user)
 }
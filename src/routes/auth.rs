@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::{header::SET_COOKIE, request::Parts, HeaderMap, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+use headers::{Cookie, HeaderMapExt};
+
+use crate::{
+    db::users::{self, User, SESSION_TTL},
+    routes::error::AppError,
+};
+
+/// Name of the cookie holding the session token.
+const SESSION_COOKIE: &str = "session";
+
+/// The logged in user, from the session cookie.
+///
+/// Rejects anonymous requests with `401 Unauthorized`; take an
+/// `Option<CurrentUser>` where logging in is optional.
+pub struct CurrentUser(pub User);
+
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .typed_get::<Cookie>()
+        .and_then(|cookie| cookie.get(SESSION_COOKIE).map(String::from))
+}
+
+impl OptionalFromRequestParts<Arc<crate::AppState>> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<crate::AppState>,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let Some(token) = session_token(&parts.headers) else {
+            return Ok(None);
+        };
+        Ok(users::session_user(&state.db, &token)
+            .await?
+            .map(CurrentUser))
+    }
+}
+
+impl FromRequestParts<Arc<crate::AppState>> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<crate::AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        <Self as OptionalFromRequestParts<_>>::from_request_parts(parts, state)
+            .await?
+            .ok_or(AppError::Unauthorized("login required".into()))
+    }
+}
+
+/// `Set-Cookie` starting a session, or ending it when `token` is `None`.
+/// HttpOnly keeps the token away from scripts, SameSite keeps other sites
+/// from making requests with it, and Secure keeps it off plain HTTP unless
+/// the server is configured otherwise.
+pub struct SessionCookie {
+    token: Option<String>,
+    secure: bool,
+}
+
+impl SessionCookie {
+    pub fn new(state: &crate::AppState, token: Option<String>) -> Self {
+        SessionCookie {
+            token,
+            secure: state.secure_cookies,
+        }
+    }
+}
+
+impl IntoResponseParts for SessionCookie {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let secure = if self.secure { "; Secure" } else { "" };
+        let cookie = match self.token {
+            Some(token) => format!(
+                "{SESSION_COOKIE}={token}; Path=/; Max-Age={SESSION_TTL}; HttpOnly; SameSite=Lax{secure}"
+            ),
+            None => {
+                format!("{SESSION_COOKIE}=; Path=/; Max-Age=0; HttpOnly; SameSite=Lax{secure}")
+            }
+        };
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            res.headers_mut().append(SET_COOKIE, cookie);
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::FromRequestParts,
+    http::{
+        header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+        request::Parts,
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, IntoResponseParts, Response, ResponseParts},
+};
+use tracing::error;
+
+use crate::db::ingest;
+
+/// `ETag` and `Last-Modified` for responses that only change when the data
+/// is re-ingested, `None` until the first ingest.
+///
+/// As an extractor it answers `304 Not Modified` before the handler runs
+/// when the client's copy is still current; returned alongside a response it
+/// adds the validators.
+pub struct Cache(Option<Validators>);
+
+struct Validators {
+    etag: String,
+    last_modified: SystemTime,
+}
+
+impl Validators {
+    fn new(ingested_at: i64) -> Self {
+        Validators {
+            // the version covers changes to the response format
+            etag: format!("\"{}-{}\"", env!("CARGO_PKG_VERSION"), ingested_at),
+            last_modified: UNIX_EPOCH + Duration::from_secs(ingested_at.max(0) as u64),
+        }
+    }
+
+    /// `If-None-Match` takes precedence over `If-Modified-Since`.
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag)
+            });
+        }
+        headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|since| since.to_str().ok())
+            .and_then(|since| httpdate::parse_http_date(since).ok())
+            .is_some_and(|since| self.last_modified <= since)
+    }
+}
+
+impl FromRequestParts<Arc<crate::AppState>> for Cache {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<crate::AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        // without a recorded ingest, or a readable one, responses just aren't cached
+        let ingested_at = match ingest::last_ingest(&state.db).await {
+            Ok(ingested_at) => ingested_at,
+            Err(e) => {
+                error!("{e}");
+                None
+            }
+        };
+        let Some(validators) = ingested_at.map(Validators::new) else {
+            return Ok(Cache(None));
+        };
+        if validators.is_fresh(&parts.headers) {
+            return Err((StatusCode::NOT_MODIFIED, Cache(Some(validators)), ()).into_response());
+        }
+        Ok(Cache(Some(validators)))
+    }
+}
+
+impl IntoResponseParts for Cache {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let Cache(Some(validators)) = self else {
+            return Ok(res);
+        };
+        let headers = res.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&validators.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Ok(last_modified) =
+            HeaderValue::from_str(&httpdate::fmt_http_date(validators.last_modified))
+        {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        // cache, but check back every time
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("public, no-cache"));
+        Ok(res)
+    }
+}
+
+#[test]
+fn test_validators() {
+    let validators = Validators::new(1_700_000_000);
+    let mut headers = HeaderMap::new();
+    assert!(!validators.is_fresh(&headers));
+
+    headers.insert(
+        IF_MODIFIED_SINCE,
+        HeaderValue::from_static("Tue, 14 Nov 2023 22:13:20 GMT"),
+    );
+    assert!(validators.is_fresh(&headers));
+
+    // a stale tag wins over a current date
+    headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"0.0.0-1\""));
+    assert!(!validators.is_fresh(&headers));
+
+    let etag = format!("W/{}, \"other\"", validators.etag);
+    headers.insert(IF_NONE_MATCH, HeaderValue::from_str(&etag).unwrap());
+    assert!(validators.is_fresh(&headers));
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use tracing::error;
+
+use crate::db::DBError;
+
+/// Error returned by every handler. Rendered as [`ErrResponse`] with a
+/// status code matching the kind of failure.
+#[derive(Debug)]
+pub enum AppError {
+    NotFound(String),
+    BadRequest(String),
+    /// not logged in, or wrong credentials
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    /// details are logged, not sent to the client
+    Database(anyhow::Error),
+    Template(tera::Error),
+}
+
+/// Body of every error response, e.g.
+/// `{"error": "not_found", "message": "title not found"}`.
+#[derive(Serialize)]
+pub struct ErrResponse {
+    /// one of `not_found`, `bad_request`, `unauthorized`, `forbidden`,
+    /// `conflict`, `database` or `template`
+    error: &'static str,
+    message: String,
+}
+
+impl AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Database(_) | AppError::Template(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::Conflict(_) => "conflict",
+            AppError::Database(_) => "database",
+            AppError::Template(_) => "template",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::NotFound(message)
+            | AppError::BadRequest(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Conflict(message) => write!(f, "{message}"),
+            AppError::Database(_) => write!(f, "database error"),
+            AppError::Template(_) => write!(f, "could not render page"),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        match &self {
+            AppError::Database(e) => error!("{e:#}"),
+            AppError::Template(e) => error!("{e:?}"),
+            _ => {}
+        }
+        let body = ErrResponse {
+            error: self.code(),
+            message: self.to_string(),
+        };
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        if let Some(e) = e.downcast_ref::<DBError>() {
+            return match e {
+                DBError::NotFound(_) => AppError::NotFound(e.to_string()),
+                DBError::Invalid(_) => AppError::BadRequest(e.to_string()),
+                DBError::Conflict(_) => AppError::Conflict(e.to_string()),
+                DBError::Forbidden(_) => AppError::Forbidden(e.to_string()),
+            };
+        }
+        if let Some(sqlx::Error::RowNotFound) = e.downcast_ref::<sqlx::Error>() {
+            return AppError::NotFound("not found".into());
+        }
+        AppError::Database(e)
+    }
+}
+
+impl From<tera::Error> for AppError {
+    fn from(e: tera::Error) -> Self {
+        AppError::Template(e)
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(e: JsonRejection) -> Self {
+        AppError::BadRequest(e.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(e: QueryRejection) -> Self {
+        AppError::BadRequest(e.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(e: PathRejection) -> Self {
+        AppError::BadRequest(e.body_text())
+    }
+}
+
+/// `Json` extractor rejecting malformed bodies with an [`AppError`].
+#[derive(FromRequest)]
+#[from_request(via(Json), rejection(AppError))]
+pub struct AppJson<T>(pub T);
+
+/// `Query` extractor rejecting malformed query strings with an [`AppError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct AppQuery<T>(pub T);
+
+/// `Path` extractor rejecting malformed path parameters with an [`AppError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct AppPath<T>(pub T);
+
+#[test]
+fn test_error_status() {
+    let status = |e: anyhow::Error| AppError::from(e).into_response().status();
+    assert_eq!(
+        status(DBError::NotFound("title").into()),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(DBError::Invalid("cursor").into()),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        status(DBError::Conflict("username").into()),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        status(sqlx::Error::RowNotFound.into()),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(sqlx::Error::PoolTimedOut.into()),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
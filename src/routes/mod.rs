@@ -1,21 +1,18 @@
 mod api;
+mod auth;
+mod cache;
+mod error;
 mod health_check;
 mod pages;
 
 use crate::macros::router;
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
-use serde::Serialize;
 use std::sync::Arc;
 use tower_http::services::{ServeDir, ServeFile};
 
-#[derive(Serialize)]
-struct ErrResponse {
-    error: String,
-}
-
 pub fn register() -> Router<Arc<crate::AppState>> {
     router! {
         { "/hc",
@@ -23,15 +20,43 @@ pub fn register() -> Router<Arc<crate::AppState>> {
             ("/ws", get(health_check::ws_handler))
         }
         { "/api",
+            ("/titles", get(api::titles)),
+            ("/titles/{id}", get(api::title)),
+            ("/titles/{id}/similar-cast", get(api::similar_cast)),
+            ("/titles/{id}/similar", get(api::similar)),
+            ("/names", get(api::names)),
+            ("/names/{id}", get(api::name)),
+            ("/name/{id}", get(api::name)),
+            ("/names/{id}/collaborators", get(api::collaborators)),
+            // older POST routes
             ("/", post(api::root)),
-            ("/item/{id}", post(api::item))
+            ("/item/{id}", post(api::item)),
+            ("/akas/{id}", get(api::akas)),
+            ("/suggest", get(api::suggest)),
+            ("/register", post(api::register)),
+            ("/login", post(api::login)),
+            ("/logout", post(api::logout)),
+            ("/me", get(api::me)),
+            ("/lists", get(api::lists)),
+            ("/lists", post(api::create_list)),
+            ("/lists/{id}", get(api::list)),
+            ("/lists/{id}", delete(api::delete_list)),
+            ("/lists/{id}/items/{tconst}", put(api::put_list_item)),
+            ("/lists/{id}/items/{tconst}", delete(api::delete_list_item)),
+            ("/path", get(api::path)),
+            ("/series/{id}", get(api::series))
         }
         service! {
             ("/assets", ServeDir::new("assets"))
         }
         { // pages
             ("/", get(pages::root)),
-            ("/movie/{id}", get(pages::movie))
+            ("/movie/{id}", get(pages::movie)),
+            ("/person/{id}", get(pages::person)),
+            ("/search", get(pages::search)),
+            ("/path", get(pages::path)),
+            ("/series/{id}", get(pages::series)),
+            ("/lists/{id}", get(pages::list))
             fallback! { ServeFile::new("assets/404.html") }
         }
     }
@@ -1,36 +1,138 @@
 use crate::{
-    db::movie,
-    macros::{page, res},
-    routes::ErrResponse,
-};
-use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{Html, IntoResponse},
-    Json,
+    db::{
+        akas::Locale,
+        graph, lists, movie, person,
+        search::{self, Cards},
+        series, titles,
+    },
+    macros::page,
+    routes::{
+        auth::CurrentUser,
+        error::{AppError, AppPath, AppQuery},
+    },
 };
+use axum::{extract::State, response::Html};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tracing::{error, info};
+use tracing::info;
 
-pub async fn root(State(state): State<Arc<crate::AppState>>) -> impl IntoResponse {
+pub async fn root(State(state): State<Arc<crate::AppState>>) -> Result<Html<String>, AppError> {
     page!(state, "index.html")
 }
 
 pub async fn movie(
     State(state): State<Arc<crate::AppState>>,
-    Path(id): Path<String>,
-) -> impl IntoResponse {
-    info!("request {id:?}");
-    let movie = res!(
-        movie::get(&state.db, id).await,
-        (
-            StatusCode::NOT_FOUND,
-            Json(ErrResponse {
-                error: "not found".into(),
-            })
-            .into_response(),
-        )
-    );
+    AppPath(id): AppPath<String>,
+    AppQuery(locale): AppQuery<Locale>,
+) -> Result<Html<String>, AppError> {
+    info!("request {id:?} {locale:?}");
+    let movie = movie::get(&state.db, id, &locale).await?;
 
     page!(state, "movie.html", movie)
 }
+
+pub async fn person(
+    State(state): State<Arc<crate::AppState>>,
+    AppPath(id): AppPath<String>,
+) -> Result<Html<String>, AppError> {
+    info!("request {id:?}");
+    let person = person::get(&state.db, id).await?;
+
+    page!(state, "person.html", person)
+}
+
+pub async fn series(
+    State(state): State<Arc<crate::AppState>>,
+    AppPath(id): AppPath<String>,
+) -> Result<Html<String>, AppError> {
+    info!("request {id:?}");
+    let series = series::get(&state.db, id).await?;
+
+    page!(state, "series.html", series)
+}
+
+/// Results per page of `/search`.
+const SEARCH_PAGE_SIZE: i64 = 20;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    cursor: Option<String>,
+}
+pub async fn search(
+    State(state): State<Arc<crate::AppState>>,
+    AppQuery(query): AppQuery<SearchQuery>,
+) -> Result<Html<String>, AppError> {
+    info!("request {query:?}");
+    #[derive(Serialize)]
+    struct SearchPage {
+        q: String,
+        cards: Option<Cards>,
+    }
+
+    let cards = if query.q.trim().is_empty() {
+        None
+    } else {
+        let page = titles::TitleQuery::search(query.q.clone())
+            .exclude_adult(true)
+            .cursor(query.cursor)
+            .limit(SEARCH_PAGE_SIZE)
+            .fetch_page(&state.db)
+            .await?;
+        Some(search::cards(&state.db, page).await?)
+    };
+
+    page!(state, "search.html", SearchPage { q: query.q, cards })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PathQuery {
+    #[serde(default)]
+    from: String,
+    #[serde(default)]
+    to: String,
+}
+pub async fn path(
+    State(state): State<Arc<crate::AppState>>,
+    AppQuery(query): AppQuery<PathQuery>,
+) -> Result<Html<String>, AppError> {
+    info!("request {query:?}");
+    #[derive(Serialize)]
+    struct PathPage {
+        from: String,
+        to: String,
+        searched: bool,
+        connection: Option<graph::Connection>,
+    }
+
+    let searched = !query.from.is_empty() && !query.to.is_empty();
+    let connection = if searched {
+        let graph = state.graph().await?;
+        graph::connect(&state.db, graph, &query.from, &query.to).await?
+    } else {
+        None
+    };
+
+    page!(
+        state,
+        "path.html",
+        PathPage {
+            from: query.from,
+            to: query.to,
+            searched,
+            connection,
+        }
+    )
+}
+
+pub async fn list(
+    State(state): State<Arc<crate::AppState>>,
+    CurrentUser(user): CurrentUser,
+    AppPath(id): AppPath<i64>,
+) -> Result<Html<String>, AppError> {
+    info!("request {id:?}");
+    let list = lists::cards(&state.db, id, user.id).await?;
+
+    page!(state, "list.html", list)
+}